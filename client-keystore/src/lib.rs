@@ -73,6 +73,9 @@ impl LocalKeystore {
 pub trait KeystoreExt {
 	fn generate<Pair: AppPair>(&self) -> Result<Pair>;
 	fn public_keys<Public: AppPublic>(&self) -> Result<Vec<Public>>;
+	/// Secret uri of the key `public`, including the password of the keystore, e.g. to insert
+	/// the key into the keystore of a node with `author_insertKey`.
+	fn secret_uri<Public: AppPublic>(&self, public: &Public) -> Result<Option<String>>;
 }
 
 impl KeystoreExt for LocalKeystore {
@@ -88,6 +91,15 @@ impl KeystoreExt for LocalKeystore {
 					.collect()
 			})
 	}
+
+	fn secret_uri<Public: AppPublic>(&self, public: &Public) -> Result<Option<String>> {
+		let inner = self.0.read();
+		let phrase = inner.key_phrase_by_type(public.as_slice(), Public::ID)?;
+		Ok(phrase.map(|phrase| match inner.password() {
+			Some(password) => format!("{}///{}", phrase, password),
+			None => phrase,
+		}))
+	}
 }
 
 #[async_trait]
@@ -728,6 +740,21 @@ mod tests {
 		assert_eq!(key_pair.public(), store_key_pair.public());
 	}
 
+	#[test]
+	fn generated_keys_can_be_restored_from_their_secret_uri() {
+		let temp_dir = TempDir::new().unwrap();
+		let password = SecretString::new("password".to_string());
+		let store = LocalKeystore::open(temp_dir.path(), Some(password)).unwrap();
+
+		let pair = store.generate::<ed25519::AppPair>().unwrap();
+		let suri = store.secret_uri(&pair.public()).unwrap().unwrap();
+		assert!(suri.ends_with("///password"));
+		assert_eq!(ed25519::AppPair::from_string(&suri, None).unwrap().public(), pair.public());
+
+		let unknown = sr25519::AppPair::from_string("//Alice", None).unwrap();
+		assert_eq!(store.secret_uri(&unknown.public()).unwrap(), None);
+	}
+
 	#[test]
 	fn store_ignores_files_with_invalid_name() {
 		let temp_dir = TempDir::new().unwrap();
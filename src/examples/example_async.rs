@@ -0,0 +1,80 @@
+/*
+Copyright 2019 Supercomputing Systems AG
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+///! Very simple example that shows how to use the api from a tokio runtime.
+use clap::{load_yaml, App};
+use futures::StreamExt;
+use keyring::AccountKeyring;
+use node_template_runtime::{BalancesCall, Call};
+use sp_core::crypto::Pair;
+use sp_runtime::generic::Era;
+use sp_runtime::MultiAddress;
+
+use substrate_api_client::rpc::AsyncWsRpcClient;
+use substrate_api_client::{compose_extrinsic_offline, AsyncApi, UncheckedExtrinsicV4, XtStatus};
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    env_logger::init();
+    let url = get_node_url_from_cli();
+
+    let from = AccountKeyring::Alice.pair();
+    let client = AsyncWsRpcClient::new(&url).await.unwrap();
+    let api = AsyncApi::new(client)
+        .await
+        .map(|api| api.set_signer(from))
+        .unwrap();
+
+    let to = AccountKeyring::Bob.to_account_id();
+    match api.get_account_data(&to).await.unwrap() {
+        Some(bob) => println!("[+] Bob's Free Balance is {}\n", bob.free),
+        None => println!("[+] Bob's Free Balance is 0\n"),
+    }
+
+    let mut events = api.subscribe_events().await.unwrap();
+
+    // the compose_extrinsic! macro fetches the nonce synchronously, so we compose offline
+    #[allow(clippy::redundant_clone)]
+    let xt: UncheckedExtrinsicV4<_> = compose_extrinsic_offline!(
+        api.clone().signer.unwrap(),
+        Call::Balances(BalancesCall::transfer(MultiAddress::Id(to), 1000)),
+        api.get_nonce().await.unwrap(),
+        Era::Immortal,
+        api.genesis_hash,
+        api.genesis_hash,
+        api.runtime_version.spec_version,
+        api.runtime_version.transaction_version
+    );
+
+    let blockh = api
+        .send_extrinsic(xt.hex_encode(), XtStatus::InBlock)
+        .await
+        .unwrap();
+    println!("[+] Transaction got included in block {:?}\n", blockh);
+
+    let events = events.next().await.unwrap();
+    println!("[+] Received events: {}\n", events);
+}
+
+pub fn get_node_url_from_cli() -> String {
+    let yml = load_yaml!("../../src/examples/cli.yml");
+    let matches = App::from_yaml(yml).get_matches();
+
+    let node_ip = matches.value_of("node-server").unwrap_or("ws://127.0.0.1");
+    let node_port = matches.value_of("node-port").unwrap_or("9944");
+    let url = format!("{}:{}", node_ip, node_port);
+    println!("Interacting with node on {}\n", url);
+    url
+}
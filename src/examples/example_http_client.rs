@@ -0,0 +1,57 @@
+/*
+Copyright 2019 Supercomputing Systems AG
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+///! Very simple example that shows how to talk to a node over HTTP instead of WebSockets.
+use clap::{load_yaml, App};
+use keyring::AccountKeyring;
+use sp_runtime::MultiAddress;
+
+use substrate_api_client::rpc::HttpRpcClient;
+use substrate_api_client::{Api, XtStatus};
+
+fn main() {
+    env_logger::init();
+    let url = get_node_url_from_cli();
+
+    let from = AccountKeyring::Alice.pair();
+    let client = HttpRpcClient::new(&url);
+    let api = Api::new(client).map(|api| api.set_signer(from)).unwrap();
+
+    let to = AccountKeyring::Bob.to_account_id();
+    match api.get_account_data(&to).unwrap() {
+        Some(bob) => println!("[+] Bob's Free Balance is {}\n", bob.free),
+        None => println!("[+] Bob's Free Balance is 0\n"),
+    }
+
+    // over HTTP we can only wait until the extrinsic is in the transaction pool
+    let xt = api.balance_transfer(MultiAddress::Id(to), 1000);
+    let xt_hash = api
+        .send_extrinsic(xt.hex_encode(), XtStatus::Ready)
+        .unwrap();
+    println!("[+] Extrinsic is in the pool. Hash: {:?}\n", xt_hash);
+}
+
+pub fn get_node_url_from_cli() -> String {
+    let yml = load_yaml!("../../src/examples/cli.yml");
+    let matches = App::from_yaml(yml).get_matches();
+
+    let node_ip = matches
+        .value_of("node-server")
+        .unwrap_or("http://127.0.0.1");
+    let node_port = matches.value_of("node-port").unwrap_or("9933");
+    let url = format!("{}:{}", node_ip, node_port);
+    println!("Interacting with node on {}\n", url);
+    url
+}
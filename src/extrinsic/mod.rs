@@ -27,6 +27,8 @@ pub extern crate log;
 pub mod balances;
 #[cfg(feature = "std")]
 pub mod contract;
+#[cfg(feature = "std")]
+pub mod session;
 #[cfg(feature = "staking-xt")]
 pub mod staking;
 pub mod xt_primitives;
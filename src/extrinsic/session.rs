@@ -0,0 +1,82 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! Extrinsics for `pallet-session`
+
+use codec::{Encode, Output};
+use sp_core::crypto::Pair;
+use sp_runtime::{MultiSignature, MultiSigner};
+
+use super::xt_primitives::*;
+use crate::extrinsic::CallIndex;
+use crate::{compose_extrinsic, Api, RpcClient};
+
+pub const SESSION_MODULE: &str = "Session";
+pub const SESSION_SET_KEYS: &str = "set_keys";
+pub const SESSION_PURGE_KEYS: &str = "purge_keys";
+
+/// The encoded `SessionKeys` of the runtime, as returned by `author_rotateKeys`.
+///
+/// Encodes to the keys themselves, without length prefix, like the `SessionKeys` struct does.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OpaqueKeys(pub Vec<u8>);
+
+impl Encode for OpaqueKeys {
+    fn size_hint(&self) -> usize {
+        self.0.len()
+    }
+
+    fn encode_to<T: Output + ?Sized>(&self, dest: &mut T) {
+        dest.write(&self.0)
+    }
+}
+
+pub type SessionSetKeysFn = (CallIndex, OpaqueKeys, Vec<u8>);
+pub type SessionPurgeKeysFn = CallIndex;
+
+pub type SessionSetKeysXt = UncheckedExtrinsicV4<SessionSetKeysFn>;
+pub type SessionPurgeKeysXt = UncheckedExtrinsicV4<SessionPurgeKeysFn>;
+
+impl<P, Client> Api<P, Client>
+where
+    P: Pair,
+    MultiSignature: From<P::Signature>,
+    MultiSigner: From<P::Public>,
+    Client: RpcClient,
+{
+    /// Sets the session keys of the signer, a validator's controller, for the next session.
+    /// `proof` is not checked by the common runtimes and may be empty.
+    pub fn session_set_keys(&self, keys: OpaqueKeys, proof: Vec<u8>) -> SessionSetKeysXt {
+        compose_extrinsic!(self, SESSION_MODULE, SESSION_SET_KEYS, keys, proof)
+    }
+
+    /// Removes the session keys of the signer.
+    pub fn session_purge_keys(&self) -> SessionPurgeKeysXt {
+        compose_extrinsic!(self, SESSION_MODULE, SESSION_PURGE_KEYS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opaque_keys_are_encoded_without_prefix() {
+        let call: SessionSetKeysFn = ([9, 0], OpaqueKeys(vec![1, 2, 3]), vec![]);
+        assert_eq!(call.encode(), vec![9, 0, 1, 2, 3, 0]);
+    }
+}
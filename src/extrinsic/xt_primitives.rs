@@ -45,6 +45,10 @@ impl GenericExtra {
     pub fn new(era: Era, nonce: u32) -> GenericExtra {
         GenericExtra(era, Compact(nonce), Compact(0_u128))
     }
+
+    pub fn nonce(&self) -> u32 {
+        (self.1).0
+    }
 }
 
 impl Default for GenericExtra {
@@ -114,6 +118,40 @@ where
     }
 }
 
+impl<Call> UncheckedExtrinsicV4<Call> {
+    pub fn signer(&self) -> Option<&GenericAddress> {
+        self.signature.as_ref().map(|(address, _, _)| address)
+    }
+
+    pub fn nonce(&self) -> Option<u32> {
+        self.signature.as_ref().map(|(_, _, extra)| extra.nonce())
+    }
+}
+
+impl UncheckedExtrinsicV4<Vec<u8>> {
+    /// Decodes an extrinsic without knowing the call type of the runtime. `function` holds the
+    /// encoded call, so encoding the result does not give back `encoded`.
+    pub fn decode_opaque(encoded: &[u8]) -> Result<Self, Error> {
+        let input = &mut &encoded[..];
+        let _length: Compact<u32> = Decode::decode(input)?;
+
+        let version = input.read_byte()?;
+        let is_signed = version & 0b1000_0000 != 0;
+        if version & 0b0111_1111 != V4 {
+            return Err("Invalid transaction version".into());
+        }
+
+        Ok(UncheckedExtrinsicV4 {
+            signature: if is_signed {
+                Some(Decode::decode(input)?)
+            } else {
+                None
+            },
+            function: input.to_vec(),
+        })
+    }
+}
+
 #[cfg(feature = "std")]
 impl<Call> fmt::Debug for UncheckedExtrinsicV4<Call>
 where
@@ -222,4 +260,20 @@ mod tests {
         let xt_enc = xt.encode();
         assert_eq!(xt, Decode::decode(&mut xt_enc.as_slice()).unwrap())
     }
+
+    #[test]
+    fn decode_opaque_keeps_the_encoded_call() {
+        let call = (4u8, 0u8, Compact(42u128));
+        let xt = UncheckedExtrinsicV4::new_signed(
+            call,
+            GenericAddress::default(),
+            MultiSignature::default(),
+            GenericExtra::new(Era::Immortal, 7),
+        );
+
+        let opaque = UncheckedExtrinsicV4::decode_opaque(&xt.encode()).unwrap();
+        assert_eq!(opaque.function, call.encode());
+        assert_eq!(opaque.signer(), Some(&GenericAddress::default()));
+        assert_eq!(opaque.nonce(), Some(7));
+    }
 }
@@ -0,0 +1,495 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! Asynchronous counterparts of [`RpcClient`](crate::std::RpcClient) and
+//! [`Api`](crate::std::Api).
+
+use std::collections::HashSet;
+use std::convert::TryFrom;
+#[cfg(feature = "ws-client")]
+use std::sync::mpsc::RecvError;
+
+use async_trait::async_trait;
+use codec::{Decode, Encode};
+use futures::future;
+use futures::stream::BoxStream;
+#[cfg(feature = "ws-client")]
+use futures::Stream;
+use futures::StreamExt;
+use log::{debug, info};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use sp_core::crypto::Pair;
+use sp_core::storage::StorageKey;
+use sp_runtime::generic::SignedBlock;
+use sp_runtime::traits::{Block, Header, IdentifyAccount};
+use sp_runtime::{AccountId32 as AccountId, MultiSignature, MultiSigner};
+use sp_version::RuntimeVersion;
+use transaction_payment::FeeDetails;
+
+use crate::std::rpc::methods::{
+    ChainGetBlock, ChainGetBlockHash, ChainGetFinalizedHead, ChainGetHeader,
+    PaymentQueryFeeDetails, RpcMethods, StateGetKeys, StateGetMetadata, StateGetReadProof,
+    StateGetRuntimeVersion, StateGetStorage,
+};
+#[cfg(feature = "ws-client")]
+use crate::std::rpc::ws_client::{find_event, EventsDecoder, RawEvent};
+use crate::std::rpc::{json_req, ReadProof, RpcMethod};
+use crate::std::{
+    decode, method_not_supported, ApiClientError, ApiResult, Metadata, RuntimeMetadataPrefixed,
+    XtStatus,
+};
+use crate::{utils, AccountData, AccountInfo, Balance, Hash};
+
+#[async_trait]
+pub trait AsyncRpcClient: Send + Sync {
+    /// Sends a RPC request that returns a String
+    async fn get_request(&self, jsonreq: Value) -> ApiResult<String>;
+
+    /// Sends a batch of RPC requests. The results are returned in the order of the requests,
+    /// `None` for a `null` result.
+    ///
+    /// Clients that do not support batches fall back to one request after the other.
+    async fn get_batch(&self, jsonreqs: Vec<Value>) -> ApiResult<Vec<Option<String>>> {
+        let mut results = Vec::with_capacity(jsonreqs.len());
+        for jsonreq in jsonreqs {
+            let str = self.get_request(jsonreq).await?;
+            results.push(if str == "null" { None } else { Some(str) });
+        }
+        Ok(results)
+    }
+
+    /// Send a RPC request that returns a SHA256 hash
+    async fn send_extrinsic(
+        &self,
+        xthex_prefixed: String,
+        exit_on: XtStatus,
+    ) -> ApiResult<Option<Hash>>;
+}
+
+#[async_trait]
+pub trait AsyncSubscriber {
+    /// Subscribes with `jsonreq` and returns the `result` of every notification. Dropping the
+    /// stream cancels the subscription.
+    async fn subscribe(&self, jsonreq: Value) -> ApiResult<BoxStream<'static, Value>>;
+}
+
+/// Like [`Api`](crate::std::Api), but every call that talks to the node is `async`.
+#[derive(Clone)]
+pub struct AsyncApi<P, Client>
+where
+    Client: AsyncRpcClient,
+{
+    pub signer: Option<P>,
+    pub genesis_hash: Hash,
+    pub metadata: Metadata,
+    pub runtime_version: RuntimeVersion,
+    rpc_methods: Option<HashSet<String>>,
+    client: Client,
+}
+
+impl<P, Client> AsyncApi<P, Client>
+where
+    P: Pair,
+    MultiSignature: From<P::Signature>,
+    MultiSigner: From<P::Public>,
+    Client: AsyncRpcClient,
+{
+    pub fn signer_account(&self) -> Option<AccountId> {
+        let pair = self.signer.as_ref()?;
+        let multi_signer = MultiSigner::from(pair.public());
+        Some(multi_signer.into_account())
+    }
+
+    pub async fn get_nonce(&self) -> ApiResult<u32> {
+        let account = self.signer_account().ok_or(ApiClientError::NoSigner)?;
+        self.get_account_info(&account)
+            .await
+            .map(|acc_opt| acc_opt.map_or_else(|| 0, |acc| acc.nonce))
+    }
+}
+
+impl<P, Client> AsyncApi<P, Client>
+where
+    Client: AsyncRpcClient,
+{
+    pub async fn new(client: Client) -> ApiResult<Self> {
+        let genesis_hash = Self::_get_genesis_hash(&client).await?;
+        info!("Got genesis hash: {:?}", genesis_hash);
+
+        let metadata = Metadata::try_from(Self::_get_metadata(&client).await?)?;
+        debug!("Metadata: {:?}", metadata);
+
+        let runtime_version = Self::_get_runtime_version(&client).await?;
+        info!("Runtime Version: {:?}", runtime_version);
+
+        let rpc_methods = Self::_get_rpc_methods(&client).await;
+
+        Ok(Self {
+            signer: None,
+            genesis_hash,
+            metadata,
+            runtime_version,
+            rpc_methods,
+            client,
+        })
+    }
+
+    pub fn set_signer(mut self, signer: P) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    async fn _get_genesis_hash(client: &Client) -> ApiResult<Hash> {
+        Self::_request::<ChainGetBlockHash>(client, (Some(0),))
+            .await?
+            .ok_or(ApiClientError::Genesis)
+    }
+
+    async fn _get_runtime_version(client: &Client) -> ApiResult<RuntimeVersion> {
+        Self::_request::<StateGetRuntimeVersion>(client, ())
+            .await?
+            .ok_or(ApiClientError::RuntimeVersion)
+    }
+
+    async fn _get_metadata(client: &Client) -> ApiResult<RuntimeMetadataPrefixed> {
+        let metadata = Self::_request::<StateGetMetadata>(client, ())
+            .await?
+            .ok_or(ApiClientError::MetadataFetch)?;
+        decode::metadata(&metadata.0)
+    }
+
+    /// Methods served by the node, `None` if the node does not list them.
+    async fn _get_rpc_methods(client: &Client) -> Option<HashSet<String>> {
+        match Self::_request::<RpcMethods>(client, ()).await {
+            Ok(list) => Some(list.methods.into_iter().collect()),
+            Err(e) => {
+                info!("Node does not list its RPC methods: {}", e);
+                None
+            }
+        }
+    }
+
+    // low level access
+    async fn _get_request(client: &Client, jsonreq: Value) -> ApiResult<Option<String>> {
+        let method = jsonreq["method"].as_str().unwrap_or_default().to_string();
+        let str = client
+            .get_request(jsonreq)
+            .await
+            .map_err(|e| method_not_supported(e, &method))?;
+
+        match &str[..] {
+            "null" => Ok(None),
+            _ => Ok(Some(str)),
+        }
+    }
+
+    async fn _request<M: RpcMethod>(client: &Client, params: M::Params) -> ApiResult<M::Response> {
+        let str = client
+            .get_request(M::request(params))
+            .await
+            .map_err(|e| method_not_supported(e, M::NAME))?;
+        serde_json::from_str(&str).map_err(|e| e.into())
+    }
+
+    pub async fn get_metadata(&self) -> ApiResult<RuntimeMetadataPrefixed> {
+        Self::_get_metadata(&self.client).await
+    }
+
+    pub async fn get_spec_version(&self) -> ApiResult<u32> {
+        Self::_get_runtime_version(&self.client)
+            .await
+            .map(|v| v.spec_version)
+    }
+
+    pub async fn get_genesis_hash(&self) -> ApiResult<Hash> {
+        Self::_get_genesis_hash(&self.client).await
+    }
+
+    pub async fn get_account_info(&self, address: &AccountId) -> ApiResult<Option<AccountInfo>> {
+        let storagekey = self.metadata.storage_map_key::<AccountId, AccountInfo>(
+            "System",
+            "Account",
+            address.clone(),
+        )?;
+        info!("storage key is: 0x{}", hex::encode(storagekey.0.clone()));
+        self.get_storage_by_key_hash(storagekey, None).await
+    }
+
+    pub async fn get_account_data(&self, address: &AccountId) -> ApiResult<Option<AccountData>> {
+        self.get_account_info(address)
+            .await
+            .map(|info| info.map(|i| i.data))
+    }
+
+    pub async fn get_finalized_head(&self) -> ApiResult<Option<Hash>> {
+        self.request::<ChainGetFinalizedHead>(()).await
+    }
+
+    pub async fn get_header<H>(&self, hash: Option<Hash>) -> ApiResult<Option<H>>
+    where
+        H: Header + DeserializeOwned,
+    {
+        self.request::<ChainGetHeader<H>>((hash,)).await
+    }
+
+    pub async fn get_block<B>(&self, hash: Option<Hash>) -> ApiResult<Option<B>>
+    where
+        B: Block + DeserializeOwned,
+    {
+        self.get_signed_block(hash)
+            .await
+            .map(|sb_opt| sb_opt.map(|sb| sb.block))
+    }
+
+    pub async fn get_signed_block<B>(&self, hash: Option<Hash>) -> ApiResult<Option<SignedBlock<B>>>
+    where
+        B: Block + DeserializeOwned,
+    {
+        self.request::<ChainGetBlock<B>>((hash,)).await
+    }
+
+    pub async fn get_request(&self, jsonreq: Value) -> ApiResult<Option<String>> {
+        Self::_get_request(&self.client, jsonreq).await
+    }
+
+    /// Sends the request of `M` and decodes its response.
+    pub async fn request<M: RpcMethod>(&self, params: M::Params) -> ApiResult<M::Response> {
+        if !self.supports(M::NAME) {
+            return Err(ApiClientError::MethodNotSupported(M::NAME.to_string()));
+        }
+        Self::_request::<M>(&self.client, params).await
+    }
+
+    /// Whether the node serves the RPC `method`, see [`Api::supports`](crate::std::Api::supports).
+    pub fn supports(&self, method: &str) -> bool {
+        self.rpc_methods
+            .as_ref()
+            .map_or(true, |methods| methods.contains(method))
+    }
+
+    pub async fn get_batch(&self, jsonreqs: Vec<Value>) -> ApiResult<Vec<Option<String>>> {
+        self.client.get_batch(jsonreqs).await
+    }
+
+    pub async fn get_storage_value<V: Decode>(
+        &self,
+        storage_prefix: &'static str,
+        storage_key_name: &'static str,
+        at_block: Option<Hash>,
+    ) -> ApiResult<Option<V>> {
+        let storagekey = self
+            .metadata
+            .storage_value_key(storage_prefix, storage_key_name)?;
+        info!("storage key is: 0x{}", hex::encode(storagekey.0.clone()));
+        self.get_storage_by_key_hash(storagekey, at_block).await
+    }
+
+    pub async fn get_storage_map<K: Encode, V: Decode + Clone>(
+        &self,
+        storage_prefix: &'static str,
+        storage_key_name: &'static str,
+        map_key: K,
+        at_block: Option<Hash>,
+    ) -> ApiResult<Option<V>> {
+        let storagekey =
+            self.metadata
+                .storage_map_key::<K, V>(storage_prefix, storage_key_name, map_key)?;
+        info!("storage key is: 0x{}", hex::encode(storagekey.0.clone()));
+        self.get_storage_by_key_hash(storagekey, at_block).await
+    }
+
+    pub async fn get_storage_double_map<K: Encode, Q: Encode, V: Decode + Clone>(
+        &self,
+        storage_prefix: &'static str,
+        storage_key_name: &'static str,
+        first: K,
+        second: Q,
+        at_block: Option<Hash>,
+    ) -> ApiResult<Option<V>> {
+        let storagekey = self.metadata.storage_double_map_key::<K, Q, V>(
+            storage_prefix,
+            storage_key_name,
+            first,
+            second,
+        )?;
+        info!("storage key is: 0x{}", hex::encode(storagekey.0.clone()));
+        self.get_storage_by_key_hash(storagekey, at_block).await
+    }
+
+    pub async fn get_storage_by_key_hash<V: Decode>(
+        &self,
+        key: StorageKey,
+        at_block: Option<Hash>,
+    ) -> ApiResult<Option<V>> {
+        decode::storage(self.get_opaque_storage_by_key_hash(key, at_block).await?)
+    }
+
+    /// Fetches the values of all `keys` in one batch request.
+    pub async fn get_storage_by_key_hashes<V: Decode>(
+        &self,
+        keys: Vec<StorageKey>,
+        at_block: Option<Hash>,
+    ) -> ApiResult<Vec<Option<V>>> {
+        decode::storages(
+            self.get_opaque_storage_by_key_hashes(keys, at_block)
+                .await?,
+        )
+    }
+
+    pub async fn get_opaque_storage_by_key_hashes(
+        &self,
+        keys: Vec<StorageKey>,
+        at_block: Option<Hash>,
+    ) -> ApiResult<Vec<Option<Vec<u8>>>> {
+        let jsonreqs = keys
+            .into_iter()
+            .map(|key| json_req::state_get_storage(key, at_block))
+            .collect();
+
+        decode::opaque_storages(self.get_batch(jsonreqs).await?)
+    }
+
+    pub async fn get_opaque_storage_by_key_hash(
+        &self,
+        key: StorageKey,
+        at_block: Option<Hash>,
+    ) -> ApiResult<Option<Vec<u8>>> {
+        self.request::<StateGetStorage>((key, at_block))
+            .await
+            .map(|s| s.map(|storage| storage.0))
+    }
+
+    pub async fn get_storage_proof_by_keys(
+        &self,
+        keys: Vec<StorageKey>,
+        at_block: Option<Hash>,
+    ) -> ApiResult<Option<ReadProof<Hash>>> {
+        self.request::<StateGetReadProof>((keys, at_block)).await
+    }
+
+    pub async fn get_keys(
+        &self,
+        key: StorageKey,
+        at_block: Option<Hash>,
+    ) -> ApiResult<Option<Vec<String>>> {
+        let keys = self.request::<StateGetKeys>((key, at_block)).await?;
+        Ok(keys.map(|keys| {
+            keys.into_iter()
+                .map(|key| format!("0x{}", hex::encode(key.0)))
+                .collect()
+        }))
+    }
+
+    pub async fn get_fee_details(
+        &self,
+        xthex_prefixed: &str,
+        at_block: Option<Hash>,
+    ) -> ApiResult<Option<FeeDetails<Balance>>> {
+        self.request::<PaymentQueryFeeDetails>((xthex_prefixed.to_string(), at_block))
+            .await?
+            .map(decode::fee_details)
+            .transpose()
+    }
+
+    pub fn get_existential_deposit(&self) -> ApiResult<Balance> {
+        decode::existential_deposit(&self.metadata)
+    }
+
+    pub async fn send_extrinsic(
+        &self,
+        xthex_prefixed: String,
+        exit_on: XtStatus,
+    ) -> ApiResult<Option<Hash>> {
+        debug!("sending extrinsic: {:?}", xthex_prefixed);
+        self.client.send_extrinsic(xthex_prefixed, exit_on).await
+    }
+}
+
+impl<P, Client> AsyncApi<P, Client>
+where
+    Client: AsyncRpcClient + AsyncSubscriber,
+{
+    /// Returns the encoded events of every block.
+    pub async fn subscribe_events(&self) -> ApiResult<BoxStream<'static, String>> {
+        debug!("subscribing to events");
+        let key = utils::storage_key("System", "Events");
+        let jsonreq = json_req::state_subscribe_storage(vec![key]);
+        let changes = self.client.subscribe(jsonreq).await?;
+        Ok(changes
+            .filter_map(|change_set| {
+                future::ready(change_set["changes"][0][1].as_str().map(|c| c.to_owned()))
+            })
+            .boxed())
+    }
+
+    /// Returns the header of every finalized block as JSON.
+    pub async fn subscribe_finalized_heads(&self) -> ApiResult<BoxStream<'static, String>> {
+        debug!("subscribing to finalized heads");
+        let jsonreq = json_req::chain_subscribe_finalized_heads();
+        let heads = self.client.subscribe(jsonreq).await?;
+        Ok(heads.map(|head| head.to_string()).boxed())
+    }
+}
+
+/// The events decoder comes with the WebSocket client.
+#[cfg(feature = "ws-client")]
+impl<P, Client> AsyncApi<P, Client>
+where
+    Client: AsyncRpcClient,
+{
+    /// Waits for the event `module::variant` in `events`, e.g. the stream of `subscribe_events`,
+    /// and decodes its data. Fails if the stream ends first.
+    pub async fn wait_for_event<E: Decode, S>(
+        &self,
+        module: &str,
+        variant: &str,
+        decoder: Option<EventsDecoder>,
+        events: &mut S,
+    ) -> ApiResult<E>
+    where
+        S: Stream<Item = String> + Unpin,
+    {
+        let raw = self
+            .wait_for_raw_event(module, variant, decoder, events)
+            .await?;
+        E::decode(&mut &raw.data[..]).map_err(|e| e.into())
+    }
+
+    pub async fn wait_for_raw_event<S>(
+        &self,
+        module: &str,
+        variant: &str,
+        decoder: Option<EventsDecoder>,
+        events: &mut S,
+    ) -> ApiResult<RawEvent>
+    where
+        S: Stream<Item = String> + Unpin,
+    {
+        let event_decoder = match decoder {
+            Some(d) => d,
+            None => EventsDecoder::try_from(self.metadata.clone())?,
+        };
+
+        while let Some(event_str) = events.next().await {
+            if let Some(raw) = find_event(&event_decoder, module, variant, event_str)? {
+                return Ok(raw);
+            }
+        }
+        Err(ApiClientError::Disconnected(RecvError))
+    }
+}
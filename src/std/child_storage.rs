@@ -0,0 +1,122 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! Access to child tries, like the storage of `pallet-contracts` contracts or the contributions
+//! of a crowdloan.
+//!
+//! A child trie is selected by its `ChildInfo`. For default child tries, it is derived from the
+//! trie id with `ChildInfo::new_default`.
+
+use codec::{Decode, Encode};
+use sp_core::blake2_256;
+use sp_core::storage::{ChildInfo, StorageKey};
+
+use crate::rpc::methods::{
+    ChildStateGetKeys, ChildStateGetStorage, ChildStateGetStorageHash, ChildStateGetStorageSize,
+    StateGetChildReadProof,
+};
+use crate::rpc::ReadProof;
+use crate::std::{decode, Api, ApiResult, RpcClient};
+use crate::Hash;
+
+/// Child trie holding the contributions to the crowdloan of `fund_index`, as created by
+/// polkadot's `crowdloan` pallet.
+pub fn crowdloan_child_info(fund_index: u32) -> ChildInfo {
+    let mut buf = b"crowdloan".to_vec();
+    buf.extend(fund_index.encode());
+    ChildInfo::new_default(&blake2_256(&buf))
+}
+
+impl<P, Client> Api<P, Client>
+where
+    Client: RpcClient,
+{
+    pub fn get_child_storage_by_key_hash<V: Decode>(
+        &self,
+        child: &ChildInfo,
+        key: StorageKey,
+        at_block: Option<Hash>,
+    ) -> ApiResult<Option<V>> {
+        decode::storage(self.get_opaque_child_storage_by_key_hash(child, key, at_block)?)
+    }
+
+    pub fn get_opaque_child_storage_by_key_hash(
+        &self,
+        child: &ChildInfo,
+        key: StorageKey,
+        at_block: Option<Hash>,
+    ) -> ApiResult<Option<Vec<u8>>> {
+        let params = (child.prefixed_storage_key(), key, at_block);
+        self.request::<ChildStateGetStorage>(params)
+            .map(|s| s.map(|storage| storage.0))
+    }
+
+    /// Keys of the child trie that start with `prefix`.
+    pub fn get_child_keys(
+        &self,
+        child: &ChildInfo,
+        prefix: StorageKey,
+        at_block: Option<Hash>,
+    ) -> ApiResult<Vec<StorageKey>> {
+        self.request::<ChildStateGetKeys>((child.prefixed_storage_key(), prefix, at_block))
+    }
+
+    /// Hash of the value at `key`, to compare values without fetching them.
+    pub fn get_child_storage_hash(
+        &self,
+        child: &ChildInfo,
+        key: StorageKey,
+        at_block: Option<Hash>,
+    ) -> ApiResult<Option<Hash>> {
+        self.request::<ChildStateGetStorageHash>((child.prefixed_storage_key(), key, at_block))
+    }
+
+    /// Size of the value at `key` in bytes.
+    pub fn get_child_storage_size(
+        &self,
+        child: &ChildInfo,
+        key: StorageKey,
+        at_block: Option<Hash>,
+    ) -> ApiResult<Option<u64>> {
+        self.request::<ChildStateGetStorageSize>((child.prefixed_storage_key(), key, at_block))
+    }
+
+    pub fn get_child_storage_proof_by_keys(
+        &self,
+        child: &ChildInfo,
+        keys: Vec<StorageKey>,
+        at_block: Option<Hash>,
+    ) -> ApiResult<Option<ReadProof<Hash>>> {
+        self.request::<StateGetChildReadProof>((child.prefixed_storage_key(), keys, at_block))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crowdloan_tries_are_default_child_tries() {
+        let child = crowdloan_child_info(2000);
+        let mut trie_id = b"crowdloan".to_vec();
+        trie_id.extend(&[0xd0, 0x07, 0, 0]);
+        assert_eq!(child.storage_key(), &blake2_256(&trie_id)[..]);
+        assert!(child
+            .prefixed_storage_key()
+            .starts_with(b":child_storage:default:"));
+    }
+}
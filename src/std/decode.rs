@@ -0,0 +1,115 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! Decoding of node responses, shared by [`Api`](crate::std::Api) and its async counterpart.
+
+use std::convert::TryInto;
+
+use codec::Decode;
+use sp_rpc::number::NumberOrHex;
+use transaction_payment::{FeeDetails, InclusionFee};
+
+use crate::std::{ApiClientError, ApiResult, FromHexString, Metadata, RuntimeMetadataPrefixed};
+use crate::Balance;
+
+pub fn metadata(encoded: &[u8]) -> ApiResult<RuntimeMetadataPrefixed> {
+    RuntimeMetadataPrefixed::decode(&mut &encoded[..]).map_err(|e| e.into())
+}
+
+pub fn storage<V: Decode>(storage: Option<Vec<u8>>) -> ApiResult<Option<V>> {
+    match storage {
+        Some(storage) => Ok(Some(Decode::decode(&mut storage.as_slice())?)),
+        None => Ok(None),
+    }
+}
+
+pub fn storages<V: Decode>(storages: Vec<Option<Vec<u8>>>) -> ApiResult<Vec<Option<V>>> {
+    storages.into_iter().map(storage).collect()
+}
+
+/// Decodes the hex encoded values of a `state_getStorage` batch.
+pub fn opaque_storages(results: Vec<Option<String>>) -> ApiResult<Vec<Option<Vec<u8>>>> {
+    results
+        .into_iter()
+        .map(|s| {
+            s.map(Vec::from_hex)
+                .transpose()
+                .map_err(ApiClientError::from)
+        })
+        .collect()
+}
+
+pub fn existential_deposit(metadata: &Metadata) -> ApiResult<Balance> {
+    let module = metadata.module_with_constants_by_name("Balances")?;
+    let constant_metadata = module.constant_by_name("ExistentialDeposit")?;
+    Decode::decode(&mut constant_metadata.get_value().as_slice()).map_err(|e| e.into())
+}
+
+pub fn fee_details(details: FeeDetails<NumberOrHex>) -> ApiResult<FeeDetails<Balance>> {
+    let inclusion_fee = if let Some(inclusion_fee) = details.inclusion_fee {
+        Some(inclusion_fee_with_balance(inclusion_fee)?)
+    } else {
+        None
+    };
+    let tip = details
+        .tip
+        .try_into()
+        .map_err(|_| ApiClientError::TryFromIntError)?;
+    Ok(FeeDetails { inclusion_fee, tip })
+}
+
+fn inclusion_fee_with_balance(
+    inclusion_fee: InclusionFee<NumberOrHex>,
+) -> ApiResult<InclusionFee<Balance>> {
+    Ok(InclusionFee {
+        base_fee: inclusion_fee
+            .base_fee
+            .try_into()
+            .map_err(|_| ApiClientError::TryFromIntError)?,
+        len_fee: inclusion_fee
+            .len_fee
+            .try_into()
+            .map_err(|_| ApiClientError::TryFromIntError)?,
+        adjusted_weight_fee: inclusion_fee
+            .adjusted_weight_fee
+            .try_into()
+            .map_err(|_| ApiClientError::TryFromIntError)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fee_details_keep_every_fee_component() {
+        let details = FeeDetails {
+            inclusion_fee: Some(InclusionFee {
+                base_fee: NumberOrHex::Number(1),
+                len_fee: NumberOrHex::Number(20),
+                adjusted_weight_fee: NumberOrHex::Hex(300.into()),
+            }),
+            tip: NumberOrHex::Number(4000),
+        };
+
+        let details = fee_details(details).unwrap();
+        let inclusion_fee = details.inclusion_fee.as_ref().unwrap();
+        assert_eq!(inclusion_fee.len_fee, 20);
+        assert_eq!(inclusion_fee.adjusted_weight_fee, 300);
+        assert_eq!(details.final_fee(), 4321);
+    }
+}
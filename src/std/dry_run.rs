@@ -0,0 +1,111 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! Applies extrinsics on top of a block with `system_dryRun`, without submitting them.
+//!
+//! `system_dryRun` is an unsafe RPC, so the node has to allow it with `--rpc-methods unsafe`.
+
+use codec::Decode;
+use sp_runtime::transaction_validity::TransactionValidityError;
+use sp_runtime::{ApplyExtrinsicResult, DispatchError};
+
+use crate::rpc::methods::SystemDryRun;
+use crate::std::{Api, ApiResult, FromHexString, Metadata, RpcClient};
+use crate::Hash;
+
+/// Outcome of a dry run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DryRunResult {
+    /// The extrinsic would be included and its call would succeed.
+    Success,
+    /// The extrinsic would be included, but its call would fail. Module errors are given by
+    /// name, e.g. `Balances::InsufficientBalance`.
+    DispatchFailed(String),
+    /// The extrinsic would not be included, e.g. because of its nonce or because the signer
+    /// can't pay the fees.
+    Invalid(TransactionValidityError),
+}
+
+impl DryRunResult {
+    pub fn is_success(&self) -> bool {
+        matches!(self, DryRunResult::Success)
+    }
+
+    fn new(result: ApplyExtrinsicResult, metadata: &Metadata) -> ApiResult<Self> {
+        Ok(match result {
+            Ok(Ok(())) => DryRunResult::Success,
+            Ok(Err(DispatchError::Module { index, error, .. })) => {
+                let module = metadata.module_with_errors(index)?;
+                let name = format!("{}::{}", module.name(), module.error(error)?);
+                DryRunResult::DispatchFailed(name)
+            }
+            Ok(Err(other)) => DryRunResult::DispatchFailed(format!("{:?}", other)),
+            Err(e) => DryRunResult::Invalid(e),
+        })
+    }
+}
+
+impl<P, Client> Api<P, Client>
+where
+    Client: RpcClient,
+{
+    /// Applies the extrinsic on top of `at`, the best block by default, and returns whether it
+    /// would succeed.
+    pub fn dry_run(&self, xthex_prefixed: &str, at: Option<Hash>) -> ApiResult<DryRunResult> {
+        let xt = Vec::from_hex(xthex_prefixed.to_string())?;
+        let encoded = self.request::<SystemDryRun>((xt.into(), at))?;
+        let result = ApplyExtrinsicResult::decode(&mut encoded.0.as_slice())?;
+        DryRunResult::new(result, &self.metadata)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::std::rpc::mock_node::test_metadata;
+    use crate::std::RuntimeMetadataPrefixed;
+    use sp_runtime::transaction_validity::InvalidTransaction;
+    use std::convert::TryFrom;
+
+    fn metadata() -> Metadata {
+        let metadata = RuntimeMetadataPrefixed::decode(&mut test_metadata().as_slice()).unwrap();
+        Metadata::try_from(metadata).unwrap()
+    }
+
+    #[test]
+    fn module_errors_are_named_after_their_pallet() {
+        let failed = Ok(Err(DispatchError::Module {
+            index: 1,
+            error: 2,
+            message: None,
+        }));
+        assert_eq!(
+            DryRunResult::new(failed, &metadata()).unwrap(),
+            DryRunResult::DispatchFailed("Balances::InsufficientBalance".to_string())
+        );
+
+        assert_eq!(
+            DryRunResult::new(Ok(Ok(())), &metadata()).unwrap(),
+            DryRunResult::Success
+        );
+        let invalid = Err(InvalidTransaction::Payment.into());
+        assert_eq!(
+            DryRunResult::new(invalid, &metadata()).unwrap(),
+            DryRunResult::Invalid(InvalidTransaction::Payment.into())
+        );
+    }
+}
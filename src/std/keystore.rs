@@ -0,0 +1,163 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! Generates session keys in a local keystore and hands them to the node, instead of
+//! `author_rotateKeys`.
+//!
+//! ```no_run
+//! use sp_core::crypto::Pair;
+//! use sp_core::sr25519;
+//! use substrate_api_client::rpc::WsRpcClient;
+//! use substrate_api_client::{Api, XtStatus};
+//! use substrate_client_keystore::LocalKeystore;
+//!
+//! // the session keys of the node template, as defined by its runtime
+//! mod aura {
+//!     use sp_application_crypto::{app_crypto, sr25519, KeyTypeId};
+//!     app_crypto!(sr25519, KeyTypeId(*b"aura"));
+//! }
+//! mod grandpa {
+//!     use sp_application_crypto::{app_crypto, ed25519, KeyTypeId};
+//!     app_crypto!(ed25519, KeyTypeId(*b"gran"));
+//! }
+//!
+//! let signer = sr25519::Pair::from_string("//Alice", None).unwrap();
+//! let api = Api::new(WsRpcClient::new("ws://127.0.0.1:9944"))
+//!     .unwrap()
+//!     .set_signer(signer);
+//! let keystore = LocalKeystore::open("keystore", None).unwrap();
+//!
+//! let xt = api
+//!     .generate_session_keys::<(aura::Pair, grandpa::Pair)>(&keystore)
+//!     .unwrap();
+//! api.send_extrinsic(xt.hex_encode(), XtStatus::InBlock).unwrap();
+//! ```
+
+use sp_application_crypto::{AppKey, AppPair};
+use sp_core::crypto::{Pair, Public};
+use sp_runtime::{MultiSignature, MultiSigner};
+use substrate_client_keystore::{KeystoreExt, LocalKeystore};
+
+use crate::extrinsic::session::{OpaqueKeys, SessionSetKeysXt};
+use crate::std::{Api, ApiClientError, ApiResult, RpcClient};
+
+/// The app crypto pairs of a runtime's `SessionKeys`, as a tuple in the order of its fields, e.g.
+/// `(aura::Pair, grandpa::Pair)`.
+pub trait SessionKeyPairs {
+    /// Generates one key per pair and returns the public keys, encoded like the `SessionKeys`.
+    fn generate<P, Client: RpcClient>(
+        api: &Api<P, Client>,
+        keystore: &LocalKeystore,
+    ) -> ApiResult<Vec<u8>>;
+}
+
+macro_rules! impl_session_key_pairs {
+    ($($pair:ident),+) => {
+        impl<$($pair: AppPair),+> SessionKeyPairs for ($($pair,)+) {
+            fn generate<P, Client: RpcClient>(
+                api: &Api<P, Client>,
+                keystore: &LocalKeystore,
+            ) -> ApiResult<Vec<u8>> {
+                let mut keys = Vec::new();
+                $(keys.extend(api.generate_session_key::<$pair>(keystore)?.to_raw_vec());)+
+                Ok(keys)
+            }
+        }
+    };
+}
+
+impl_session_key_pairs!(A);
+impl_session_key_pairs!(A, B);
+impl_session_key_pairs!(A, B, C);
+impl_session_key_pairs!(A, B, C, D);
+impl_session_key_pairs!(A, B, C, D, E);
+impl_session_key_pairs!(A, B, C, D, E, F);
+impl_session_key_pairs!(A, B, C, D, E, F, G);
+impl_session_key_pairs!(A, B, C, D, E, F, G, H);
+
+fn keystore_error(e: impl std::fmt::Display) -> ApiClientError {
+    ApiClientError::Keystore(e.to_string())
+}
+
+impl<P, Client> Api<P, Client>
+where
+    Client: RpcClient,
+{
+    /// Generates a key of `KeyPair`'s key type in `keystore` and inserts it into the node's
+    /// keystore.
+    pub fn generate_session_key<KeyPair: AppPair>(
+        &self,
+        keystore: &LocalKeystore,
+    ) -> ApiResult<<KeyPair as AppKey>::Public> {
+        let public = keystore
+            .generate::<KeyPair>()
+            .map_err(keystore_error)?
+            .public();
+        let suri = keystore
+            .secret_uri(&public)
+            .map_err(keystore_error)?
+            .ok_or_else(|| keystore_error("generated key is missing in the keystore"))?;
+        self.insert_key(KeyPair::ID, &suri, public.as_slice())?;
+        Ok(public)
+    }
+}
+
+impl<P, Client> Api<P, Client>
+where
+    P: Pair,
+    MultiSignature: From<P::Signature>,
+    MultiSigner: From<P::Public>,
+    Client: RpcClient,
+{
+    /// Generates the session keys `Keys` in `keystore` and inserts them into the node's
+    /// keystore. Returns the `Session::set_keys` extrinsic for the new keys.
+    pub fn generate_session_keys<Keys: SessionKeyPairs>(
+        &self,
+        keystore: &LocalKeystore,
+    ) -> ApiResult<SessionSetKeysXt> {
+        let keys = Keys::generate(self, keystore)?;
+        Ok(self.session_set_keys(OpaqueKeys(keys), Vec::new()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::std::rpc::mock_node::test_metadata;
+    use crate::std::rpc::MockNode;
+    use serde_json::Value;
+    use sp_application_crypto::{ed25519, sr25519};
+
+    #[test]
+    fn session_keys_are_generated_in_the_keystore() {
+        let node = MockNode::new(test_metadata());
+        node.set_response("author_insertKey", Value::Null);
+        let api: Api<sp_core::sr25519::Pair, _> = Api::new(node).unwrap();
+        let keystore = LocalKeystore::in_memory();
+
+        let keys = <(sr25519::AppPair, ed25519::AppPair)>::generate(&api, &keystore).unwrap();
+
+        let first = keystore.public_keys::<sr25519::AppPublic>().unwrap();
+        let second = keystore.public_keys::<ed25519::AppPublic>().unwrap();
+        assert_eq!((first.len(), second.len()), (1, 1));
+        assert_eq!(
+            keys,
+            [first[0].to_raw_vec(), second[0].to_raw_vec()].concat()
+        );
+        assert!(keystore.secret_uri(&first[0]).unwrap().is_some());
+    }
+}
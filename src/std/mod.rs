@@ -1,6 +1,6 @@
 pub use metadata::RuntimeMetadataPrefixed;
 pub use serde_json::Value;
-pub use sp_core::crypto::Pair;
+pub use sp_core::crypto::{KeyTypeId, Pair};
 pub use sp_core::storage::StorageKey;
 pub use sp_runtime::traits::{Block, Header};
 pub use sp_runtime::{
@@ -11,24 +11,57 @@ pub use sp_std::prelude::*;
 pub use sp_version::RuntimeVersion;
 pub use transaction_payment::FeeDetails;
 
+pub use crate::std::dry_run::DryRunResult;
 pub use crate::std::rpc::XtStatus;
+pub use crate::std::storage_history::StorageChange;
+pub use crate::std::storage_iter::StorageMapIter;
 pub use crate::utils::FromHexString;
 pub use node_metadata::Metadata;
 
+#[cfg(feature = "async")]
+pub use async_api::{AsyncApi, AsyncRpcClient, AsyncSubscriber};
+
+#[cfg(feature = "async")]
+pub mod async_api;
+pub mod child_storage;
+mod decode;
+pub mod dry_run;
+#[cfg(feature = "keystore")]
+pub mod keystore;
 pub mod rpc;
+pub mod runtime_api;
+pub mod storage_history;
+pub mod storage_iter;
 
 mod node_metadata;
 
-use std::convert::{TryFrom, TryInto};
+use std::collections::HashSet;
+use std::convert::TryFrom;
 
 use codec::{Decode, Encode};
 use log::{debug, info};
 use serde::de::DeserializeOwned;
-use sp_rpc::number::NumberOrHex;
-use transaction_payment::InclusionFee;
+use sp_core::sr25519;
+use sp_runtime::generic::Era;
+use transaction_payment::RuntimeDispatchInfo;
 
+use crate::extrinsic::session::OpaqueKeys;
 use crate::rpc::json_req;
-use crate::{extrinsic, Balance};
+use crate::rpc::methods::{
+    AuthorHasKey, AuthorHasSessionKeys, AuthorInsertKey, AuthorPendingExtrinsics,
+    AuthorRemoveExtrinsic, AuthorRotateKeys, ChainGetBlock, ChainGetBlockHash,
+    ChainGetFinalizedHead, ChainGetHeader, OffchainLocalStorageGet, OffchainLocalStorageSet,
+    PaymentQueryFeeDetails, PaymentQueryInfo, RpcMethods, StateGetKeys, StateGetMetadata,
+    StateGetReadProof, StateGetRuntimeVersion, StateGetStorage, SystemChain, SystemChainType,
+    SystemHealth, SystemLocalPeerId, SystemName, SystemPeers, SystemProperties, SystemSyncState,
+    SystemVersion,
+};
+use crate::rpc::RpcMethod;
+use crate::rpc::{
+    ChainProperties, ChainType, ExtrinsicOrHash, Health, PeerInfo, PendingExtrinsic, PoolNonces,
+    StorageKind, SyncState,
+};
+use crate::{extrinsic, Balance, GenericAddress};
 use crate::{AccountData, AccountInfo, Hash};
 
 pub type ApiResult<T> = Result<T, ApiClientError>;
@@ -37,8 +70,36 @@ pub trait RpcClient {
     /// Sends a RPC request that returns a String
     fn get_request(&self, jsonreq: serde_json::Value) -> ApiResult<String>;
 
+    /// Sends a batch of RPC requests. The results are returned in the order of the requests,
+    /// `None` for a `null` result.
+    ///
+    /// Clients that do not support batches fall back to one request after the other.
+    fn get_batch(&self, jsonreqs: Vec<serde_json::Value>) -> ApiResult<Vec<Option<String>>> {
+        jsonreqs
+            .into_iter()
+            .map(|jsonreq| {
+                self.get_request(jsonreq)
+                    .map(|str| if str == "null" { None } else { Some(str) })
+            })
+            .collect()
+    }
+
     /// Send a RPC request that returns a SHA256 hash
     fn send_extrinsic(&self, xthex_prefixed: String, exit_on: XtStatus) -> ApiResult<Option<Hash>>;
+
+    /// Like `send_extrinsic`, but hands every status update of the watch to `on_status`, in the
+    /// order the node sent them, e.g. `"ready"` or `{"inBlock": "0x…"}`.
+    ///
+    /// Clients that don't watch extrinsics report no updates.
+    fn watch_extrinsic(
+        &self,
+        xthex_prefixed: String,
+        exit_on: XtStatus,
+        on_status: &mut dyn FnMut(&serde_json::Value),
+    ) -> ApiResult<Option<Hash>> {
+        let _ = on_status;
+        self.send_extrinsic(xthex_prefixed, exit_on)
+    }
 }
 
 #[derive(Clone)]
@@ -50,6 +111,8 @@ where
     pub genesis_hash: Hash,
     pub metadata: Metadata,
     pub runtime_version: RuntimeVersion,
+    dry_run_before_submit: bool,
+    rpc_methods: Option<HashSet<String>>,
     client: Client,
 }
 
@@ -74,6 +137,20 @@ where
         self.get_account_info(&self.signer_account().unwrap())
             .map(|acc_opt| acc_opt.map_or_else(|| 0, |acc| acc.nonce))
     }
+
+    /// Nonces of the signer's extrinsics in the transaction pool, to find the ones that are stuck
+    /// behind a missing nonce.
+    pub fn pool_nonces(&self) -> ApiResult<PoolNonces> {
+        let account = self.signer_account().ok_or(ApiClientError::NoSigner)?;
+        let signer = GenericAddress::from(account);
+        let pool = self
+            .pending_extrinsics()?
+            .into_iter()
+            .filter(|pending| pending.extrinsic.signer() == Some(&signer))
+            .filter_map(|pending| pending.extrinsic.nonce())
+            .collect();
+        Ok(PoolNonces::new(self.get_nonce()?, pool))
+    }
 }
 
 impl<P, Client> Api<P, Client>
@@ -90,11 +167,15 @@ where
         let runtime_version = Self::_get_runtime_version(&client)?;
         info!("Runtime Version: {:?}", runtime_version);
 
+        let rpc_methods = Self::_get_rpc_methods(&client);
+
         Ok(Self {
             signer: None,
             genesis_hash,
             metadata,
             runtime_version,
+            dry_run_before_submit: false,
+            rpc_methods,
             client,
         })
     }
@@ -104,40 +185,44 @@ where
         self
     }
 
-    fn _get_genesis_hash(client: &Client) -> ApiResult<Hash> {
-        let jsonreq = json_req::chain_get_genesis_hash();
-        let genesis = Self::_get_request(client, jsonreq)?;
+    /// Dry-runs extrinsics in `send_extrinsic` and only submits the ones that would succeed.
+    /// Saves the fees of failing extrinsics, at the cost of an extra request.
+    pub fn set_dry_run_before_submit(mut self, dry_run: bool) -> Self {
+        self.dry_run_before_submit = dry_run;
+        self
+    }
 
-        match genesis {
-            Some(g) => Hash::from_hex(g).map_err(|e| e.into()),
-            None => Err(ApiClientError::Genesis),
-        }
+    fn _get_genesis_hash(client: &Client) -> ApiResult<Hash> {
+        Self::_request::<ChainGetBlockHash>(client, (Some(0),))?.ok_or(ApiClientError::Genesis)
     }
 
     fn _get_runtime_version(client: &Client) -> ApiResult<RuntimeVersion> {
-        let jsonreq = json_req::state_get_runtime_version();
-        let version = Self::_get_request(client, jsonreq)?;
-
-        match version {
-            Some(v) => serde_json::from_str(&v).map_err(|e| e.into()),
-            None => Err(ApiClientError::RuntimeVersion),
-        }
+        Self::_request::<StateGetRuntimeVersion>(client, ())?.ok_or(ApiClientError::RuntimeVersion)
     }
 
     fn _get_metadata(client: &Client) -> ApiResult<RuntimeMetadataPrefixed> {
-        let jsonreq = json_req::state_get_metadata();
-        let meta = Self::_get_request(client, jsonreq)?;
-
-        if meta.is_none() {
-            return Err(ApiClientError::MetadataFetch);
+        let metadata =
+            Self::_request::<StateGetMetadata>(client, ())?.ok_or(ApiClientError::MetadataFetch)?;
+        decode::metadata(&metadata.0)
+    }
+
+    /// Methods served by the node, `None` if the node does not list them.
+    fn _get_rpc_methods(client: &Client) -> Option<HashSet<String>> {
+        match Self::_request::<RpcMethods>(client, ()) {
+            Ok(list) => Some(list.methods.into_iter().collect()),
+            Err(e) => {
+                info!("Node does not list its RPC methods: {}", e);
+                None
+            }
         }
-        let metadata = Vec::from_hex(meta.unwrap())?;
-        RuntimeMetadataPrefixed::decode(&mut metadata.as_slice()).map_err(|e| e.into())
     }
 
     // low level access
     fn _get_request(client: &Client, jsonreq: Value) -> ApiResult<Option<String>> {
-        let str = client.get_request(jsonreq)?;
+        let method = jsonreq["method"].as_str().unwrap_or_default().to_string();
+        let str = client
+            .get_request(jsonreq)
+            .map_err(|e| method_not_supported(e, &method))?;
 
         match &str[..] {
             "null" => Ok(None),
@@ -145,6 +230,13 @@ where
         }
     }
 
+    fn _request<M: RpcMethod>(client: &Client, params: M::Params) -> ApiResult<M::Response> {
+        let str = client
+            .get_request(M::request(params))
+            .map_err(|e| method_not_supported(e, M::NAME))?;
+        serde_json::from_str(&str).map_err(|e| e.into())
+    }
+
     pub fn get_metadata(&self) -> ApiResult<RuntimeMetadataPrefixed> {
         Self::_get_metadata(&self.client)
     }
@@ -166,28 +258,28 @@ where
         self.get_storage_by_key_hash(storagekey, None)
     }
 
+    /// Fetches the account infos of all `addresses` in one batch request.
+    pub fn get_account_infos(
+        &self,
+        addresses: &[AccountId],
+    ) -> ApiResult<Vec<Option<AccountInfo>>> {
+        self.get_storage_map_batch("System", "Account", addresses.to_vec(), None)
+    }
+
     pub fn get_account_data(&self, address: &AccountId) -> ApiResult<Option<AccountData>> {
         self.get_account_info(address)
             .map(|info| info.map(|i| i.data))
     }
 
     pub fn get_finalized_head(&self) -> ApiResult<Option<Hash>> {
-        let h = self.get_request(json_req::chain_get_finalized_head())?;
-        match h {
-            Some(hash) => Ok(Some(Hash::from_hex(hash)?)),
-            None => Ok(None),
-        }
+        self.request::<ChainGetFinalizedHead>(())
     }
 
     pub fn get_header<H>(&self, hash: Option<Hash>) -> ApiResult<Option<H>>
     where
         H: Header + DeserializeOwned,
     {
-        let h = self.get_request(json_req::chain_get_header(hash))?;
-        match h {
-            Some(hash) => Ok(Some(serde_json::from_str(&hash)?)),
-            None => Ok(None),
-        }
+        self.request::<ChainGetHeader<H>>((hash,))
     }
 
     pub fn get_block<B>(&self, hash: Option<Hash>) -> ApiResult<Option<B>>
@@ -205,17 +297,34 @@ where
     where
         B: Block + DeserializeOwned,
     {
-        let b = self.get_request(json_req::chain_get_block(hash))?;
-        match b {
-            Some(block) => Ok(Some(serde_json::from_str(&block)?)),
-            None => Ok(None),
-        }
+        self.request::<ChainGetBlock<B>>((hash,))
     }
 
     pub fn get_request(&self, jsonreq: Value) -> ApiResult<Option<String>> {
         Self::_get_request(&self.client, jsonreq)
     }
 
+    /// Sends the request of `M` and decodes its response.
+    pub fn request<M: RpcMethod>(&self, params: M::Params) -> ApiResult<M::Response> {
+        if !self.supports(M::NAME) {
+            return Err(ApiClientError::MethodNotSupported(M::NAME.to_string()));
+        }
+        Self::_request::<M>(&self.client, params)
+    }
+
+    /// Whether the node serves the RPC `method`, e.g. `payment_queryInfo`. Archive and pruned
+    /// nodes or nodes denying unsafe RPCs serve different methods. Nodes that do not list their
+    /// methods with `rpc_methods` are assumed to serve all of them.
+    pub fn supports(&self, method: &str) -> bool {
+        self.rpc_methods
+            .as_ref()
+            .map_or(true, |methods| methods.contains(method))
+    }
+
+    pub fn get_batch(&self, jsonreqs: Vec<Value>) -> ApiResult<Vec<Option<String>>> {
+        self.client.get_batch(jsonreqs)
+    }
+
     pub fn get_storage_value<V: Decode>(
         &self,
         storage_prefix: &'static str,
@@ -243,6 +352,24 @@ where
         self.get_storage_by_key_hash(storagekey, at_block)
     }
 
+    /// Fetches the values of all `map_keys` in one batch request.
+    pub fn get_storage_map_batch<K: Encode, V: Decode + Clone>(
+        &self,
+        storage_prefix: &'static str,
+        storage_key_name: &'static str,
+        map_keys: Vec<K>,
+        at_block: Option<Hash>,
+    ) -> ApiResult<Vec<Option<V>>> {
+        let storagekeys = map_keys
+            .into_iter()
+            .map(|map_key| {
+                self.metadata
+                    .storage_map_key::<K, V>(storage_prefix, storage_key_name, map_key)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        self.get_storage_by_key_hashes(storagekeys, at_block)
+    }
+
     pub fn get_storage_map_key_prefix(
         &self,
         storage_prefix: &'static str,
@@ -276,11 +403,29 @@ where
         key: StorageKey,
         at_block: Option<Hash>,
     ) -> ApiResult<Option<V>> {
-        let s = self.get_opaque_storage_by_key_hash(key, at_block)?;
-        match s {
-            Some(storage) => Ok(Some(Decode::decode(&mut storage.as_slice())?)),
-            None => Ok(None),
-        }
+        decode::storage(self.get_opaque_storage_by_key_hash(key, at_block)?)
+    }
+
+    /// Fetches the values of all `keys` in one batch request.
+    pub fn get_storage_by_key_hashes<V: Decode>(
+        &self,
+        keys: Vec<StorageKey>,
+        at_block: Option<Hash>,
+    ) -> ApiResult<Vec<Option<V>>> {
+        decode::storages(self.get_opaque_storage_by_key_hashes(keys, at_block)?)
+    }
+
+    pub fn get_opaque_storage_by_key_hashes(
+        &self,
+        keys: Vec<StorageKey>,
+        at_block: Option<Hash>,
+    ) -> ApiResult<Vec<Option<Vec<u8>>>> {
+        let jsonreqs = keys
+            .into_iter()
+            .map(|key| json_req::state_get_storage(key, at_block))
+            .collect();
+
+        decode::opaque_storages(self.get_batch(jsonreqs)?)
     }
 
     pub fn get_opaque_storage_by_key_hash(
@@ -288,13 +433,46 @@ where
         key: StorageKey,
         at_block: Option<Hash>,
     ) -> ApiResult<Option<Vec<u8>>> {
-        let jsonreq = json_req::state_get_storage(key, at_block);
-        let s = self.get_request(jsonreq)?;
+        self.request::<StateGetStorage>((key, at_block))
+            .map(|s| s.map(|storage| storage.0))
+    }
 
-        match s {
-            Some(storage) => Ok(Some(Vec::from_hex(storage)?)),
-            None => Ok(None),
-        }
+    /// Value of `key` in the node's offchain storage, as written by offchain workers. The
+    /// `offchain_*` RPCs are unsafe, so the node has to allow them with `--rpc-methods unsafe`.
+    pub fn offchain_storage_get<V: Decode>(
+        &self,
+        kind: StorageKind,
+        key: &[u8],
+    ) -> ApiResult<Option<V>> {
+        decode::storage(self.get_opaque_offchain_storage(kind, key)?)
+    }
+
+    pub fn get_opaque_offchain_storage(
+        &self,
+        kind: StorageKind,
+        key: &[u8],
+    ) -> ApiResult<Option<Vec<u8>>> {
+        self.request::<OffchainLocalStorageGet>((kind, key.to_vec().into()))
+            .map(|s| s.map(|storage| storage.0))
+    }
+
+    /// Sets `key` in the node's offchain storage to the encoded `value`.
+    pub fn offchain_storage_set<V: Encode>(
+        &self,
+        kind: StorageKind,
+        key: &[u8],
+        value: &V,
+    ) -> ApiResult<()> {
+        self.set_opaque_offchain_storage(kind, key, value.encode())
+    }
+
+    pub fn set_opaque_offchain_storage(
+        &self,
+        kind: StorageKind,
+        key: &[u8],
+        value: Vec<u8>,
+    ) -> ApiResult<()> {
+        self.request::<OffchainLocalStorageSet>((kind, key.to_vec().into(), value.into()))
     }
 
     pub fn get_storage_value_proof(
@@ -347,12 +525,7 @@ where
         keys: Vec<StorageKey>,
         at_block: Option<Hash>,
     ) -> ApiResult<Option<rpc::ReadProof<Hash>>> {
-        let jsonreq = json_req::state_get_read_proof(keys, at_block);
-        let p = self.get_request(jsonreq)?;
-        match p {
-            Some(proof) => Ok(Some(serde_json::from_str(&proof)?)),
-            None => Ok(None),
-        }
+        self.request::<StateGetReadProof>((keys, at_block))
     }
 
     pub fn get_keys(
@@ -360,12 +533,12 @@ where
         key: StorageKey,
         at_block: Option<Hash>,
     ) -> ApiResult<Option<Vec<String>>> {
-        let jsonreq = json_req::state_get_keys(key, at_block);
-        let k = self.get_request(jsonreq)?;
-        match k {
-            Some(keys) => Ok(Some(serde_json::from_str(&keys)?)),
-            None => Ok(None),
-        }
+        let keys = self.request::<StateGetKeys>((key, at_block))?;
+        Ok(keys.map(|keys| {
+            keys.into_iter()
+                .map(|key| format!("0x{}", hex::encode(key.0)))
+                .collect()
+        }))
     }
 
     pub fn get_fee_details(
@@ -373,21 +546,130 @@ where
         xthex_prefixed: &str,
         at_block: Option<Hash>,
     ) -> ApiResult<Option<FeeDetails<Balance>>> {
-        let jsonreq = json_req::payment_query_fee_details(xthex_prefixed, at_block);
-        let res = self.get_request(jsonreq)?;
-        match res {
-            Some(details) => {
-                let details: FeeDetails<NumberOrHex> = serde_json::from_str(&details)?;
-                let details = convert_fee_details(details)?;
-                Ok(Some(details))
-            }
-            None => Ok(None),
-        }
+        self.request::<PaymentQueryFeeDetails>((xthex_prefixed.to_string(), at_block))?
+            .map(decode::fee_details)
+            .transpose()
+    }
+
+    /// Weight, dispatch class and fee of the extrinsic, without the tip.
+    pub fn get_payment_info(
+        &self,
+        xthex_prefixed: &str,
+        at_block: Option<Hash>,
+    ) -> ApiResult<RuntimeDispatchInfo<Balance>> {
+        self.request::<PaymentQueryInfo>((xthex_prefixed.to_string(), at_block))
+    }
+
+    /// Estimates the fee of an extrinsic with `call`, e.g. one composed with `compose_call!`.
+    ///
+    /// The call is wrapped into an extrinsic signed by a throwaway sr25519 key, so no signer is
+    /// needed. Fees depend on the length of the extrinsic, so pass the `nonce` and `era` the real
+    /// extrinsic will have: both are encoded with a variable length. Extrinsics signed with ecdsa
+    /// are one byte longer than the estimated one.
+    pub fn estimate_fee<Call: Encode + Clone>(
+        &self,
+        call: Call,
+        nonce: u32,
+        era: Era,
+        at_block: Option<Hash>,
+    ) -> ApiResult<Balance> {
+        let signer = sr25519::Pair::from_seed(&[0; 32]);
+        // the signature is not checked, so mortal eras can be checkpointed at any block
+        let xt = crate::compose_extrinsic_offline!(
+            signer,
+            call,
+            nonce,
+            era,
+            self.genesis_hash,
+            self.genesis_hash,
+            self.runtime_version.spec_version,
+            self.runtime_version.transaction_version
+        );
+        self.get_payment_info(&xt.hex_encode(), at_block)
+            .map(|info| info.partial_fee)
+    }
+
+    /// Name of the node implementation, e.g. `Substrate Node`.
+    pub fn get_system_name(&self) -> ApiResult<String> {
+        self.request::<SystemName>(())
+    }
+
+    pub fn get_system_version(&self) -> ApiResult<String> {
+        self.request::<SystemVersion>(())
+    }
+
+    /// Name of the chain from the chain spec.
+    pub fn get_system_chain(&self) -> ApiResult<String> {
+        self.request::<SystemChain>(())
+    }
+
+    pub fn get_system_chain_type(&self) -> ApiResult<ChainType> {
+        self.request::<SystemChainType>(())
+    }
+
+    pub fn get_system_properties(&self) -> ApiResult<ChainProperties> {
+        self.request::<SystemProperties>(())
+    }
+
+    pub fn get_system_health(&self) -> ApiResult<Health> {
+        self.request::<SystemHealth>(())
+    }
+
+    pub fn get_system_peers(&self) -> ApiResult<Vec<PeerInfo>> {
+        self.request::<SystemPeers>(())
+    }
+
+    pub fn get_system_sync_state(&self) -> ApiResult<SyncState> {
+        self.request::<SystemSyncState>(())
     }
+
+    /// Base58 encoded libp2p peer id of the node.
+    pub fn get_system_local_peer_id(&self) -> ApiResult<String> {
+        self.request::<SystemLocalPeerId>(())
+    }
+
+    /// Extrinsics waiting in the transaction pool of the node.
+    pub fn pending_extrinsics(&self) -> ApiResult<Vec<PendingExtrinsic>> {
+        self.request::<AuthorPendingExtrinsics>(())?
+            .iter()
+            .map(|encoded| PendingExtrinsic::decode(&encoded.0).map_err(ApiClientError::from))
+            .collect()
+    }
+
+    /// Removes extrinsics from the transaction pool, together with the ones depending on them.
+    /// Returns the hashes of all removed extrinsics.
+    pub fn remove_extrinsics(&self, extrinsics: Vec<ExtrinsicOrHash>) -> ApiResult<Vec<Hash>> {
+        self.request::<AuthorRemoveExtrinsic>((extrinsics,))
+    }
+
+    /// Generates new session keys in the node's keystore. Returns the public keys, encoded as
+    /// expected by `session_set_keys`.
+    pub fn rotate_keys(&self) -> ApiResult<OpaqueKeys> {
+        self.request::<AuthorRotateKeys>(())
+            .map(|keys| OpaqueKeys(keys.0))
+    }
+
+    /// Inserts the key pair of `suri` into the node's keystore.
+    pub fn insert_key(&self, key_type: KeyTypeId, suri: &str, public: &[u8]) -> ApiResult<()> {
+        let params = (
+            key_type_name(key_type)?,
+            suri.to_string(),
+            public.to_vec().into(),
+        );
+        self.request::<AuthorInsertKey>(params)
+    }
+
+    /// Whether the node's keystore holds the private keys of all `keys`.
+    pub fn has_session_keys(&self, keys: &OpaqueKeys) -> ApiResult<bool> {
+        self.request::<AuthorHasSessionKeys>((keys.0.clone().into(),))
+    }
+
+    pub fn has_key(&self, public: &[u8], key_type: KeyTypeId) -> ApiResult<bool> {
+        self.request::<AuthorHasKey>((public.to_vec().into(), key_type_name(key_type)?))
+    }
+
     pub fn get_existential_deposit(&self) -> ApiResult<Balance> {
-        let module = self.metadata.module_with_constants_by_name("Balances")?;
-        let constant_metadata = module.constant_by_name("ExistentialDeposit")?;
-        Decode::decode(&mut constant_metadata.get_value().as_slice()).map_err(|e| e.into())
+        decode::existential_deposit(&self.metadata)
     }
 
     #[cfg(feature = "ws-client")]
@@ -397,47 +679,78 @@ where
         exit_on: XtStatus,
     ) -> ApiResult<Option<Hash>> {
         debug!("sending extrinsic: {:?}", xthex_prefixed);
+        self.check_dry_run(&xthex_prefixed)?;
         self.client.send_extrinsic(xthex_prefixed, exit_on)
     }
 
     #[cfg(not(feature = "ws-client"))]
     pub fn send_extrinsic(&self, xthex_prefixed: String) -> ApiResult<Option<Hash>> {
         debug!("sending extrinsic: {:?}", xthex_prefixed);
-        // XtStatus should never be used used but we need to put something
-        self.client
-            .send_extrinsic(xthex_prefixed, XtStatus::Broadcast)
+        self.check_dry_run(&xthex_prefixed)?;
+        // clients without subscriptions only support returning once the extrinsic is in the pool
+        self.client.send_extrinsic(xthex_prefixed, XtStatus::Ready)
+    }
+
+    fn check_dry_run(&self, xthex_prefixed: &str) -> ApiResult<()> {
+        if !self.dry_run_before_submit {
+            return Ok(());
+        }
+        match self.dry_run(xthex_prefixed, None)? {
+            DryRunResult::Success => Ok(()),
+            failed => Err(ApiClientError::DryRunFailed(failed)),
+        }
+    }
+}
+
+impl ApiClientError {
+    /// Whether the error was caused by the connection to the node rather than by the request.
+    pub fn is_transport_error(&self) -> bool {
+        match self {
+            #[cfg(feature = "ws-client")]
+            ApiClientError::WebSocket(_) => true,
+            #[cfg(feature = "http-client")]
+            ApiClientError::Http(_) => true,
+            #[cfg(feature = "async")]
+            ApiClientError::AsyncWebSocket(_) => true,
+            ApiClientError::Disconnected(_) | ApiClientError::Timeout => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the request failed before it was sent to the node, e.g. because the connection
+    /// could not be opened. Unlike after other transport errors, the node has certainly not
+    /// run it.
+    pub fn is_unsent(&self) -> bool {
+        match self {
+            #[cfg(feature = "ws-client")]
+            ApiClientError::WebSocket(_) => true,
+            #[cfg(feature = "http-client")]
+            ApiClientError::Http(e) => matches!(
+                e.kind(),
+                ureq::ErrorKind::Dns | ureq::ErrorKind::ConnectionFailed
+            ),
+            _ => false,
+        }
     }
 }
 
-fn convert_fee_details(details: FeeDetails<NumberOrHex>) -> ApiResult<FeeDetails<u128>> {
-    let inclusion_fee = if let Some(inclusion_fee) = details.inclusion_fee {
-        Some(inclusion_fee_with_balance(inclusion_fee)?)
-    } else {
-        None
-    };
-    let tip = details
-        .tip
-        .try_into()
-        .map_err(|_| ApiClientError::TryFromIntError)?;
-    Ok(FeeDetails { inclusion_fee, tip })
+/// JSON-RPC error code for calls to methods the node does not serve.
+const METHOD_NOT_FOUND: i64 = -32601;
+
+fn method_not_supported(error: ApiClientError, method: &str) -> ApiClientError {
+    match error {
+        ApiClientError::Rpc {
+            code: METHOD_NOT_FOUND,
+            ..
+        } => ApiClientError::MethodNotSupported(method.to_string()),
+        other => other,
+    }
 }
 
-fn inclusion_fee_with_balance(
-    inclusion_fee: InclusionFee<NumberOrHex>,
-) -> ApiResult<InclusionFee<Balance>> {
-    Ok(InclusionFee {
-        base_fee: inclusion_fee
-            .base_fee
-            .try_into()
-            .map_err(|_| ApiClientError::TryFromIntError)?,
-        len_fee: inclusion_fee
-            .base_fee
-            .try_into()
-            .map_err(|_| ApiClientError::TryFromIntError)?,
-        adjusted_weight_fee: inclusion_fee
-            .base_fee
-            .try_into()
-            .map_err(|_| ApiClientError::TryFromIntError)?,
+/// Key types are sent as their four letter name, e.g. `aura`.
+fn key_type_name(key_type: KeyTypeId) -> ApiResult<String> {
+    String::from_utf8(key_type.0.to_vec()).map_err(|_| {
+        ApiClientError::RpcClient(format!("Key type {:?} is not valid UTF-8", key_type.0))
     })
 }
 
@@ -457,8 +770,25 @@ pub enum ApiClientError {
     WebSocket(#[from] ws::Error),
     #[error("RpcClient error: {0}")]
     RpcClient(String),
+    #[error("RPC error {code}: {message}: {}", .data.as_deref().unwrap_or(""))]
+    Rpc {
+        code: i64,
+        message: String,
+        data: Option<String>,
+    },
     #[error("ChannelReceiveError, sender is disconnected: {0}")]
     Disconnected(#[from] sp_std::sync::mpsc::RecvError),
+    #[error("Timed out waiting for the node to answer")]
+    Timeout,
+    #[error("Call was cancelled")]
+    Cancelled,
+    #[error("None of the endpoints is healthy")]
+    NoHealthyEndpoint,
+    #[error("Client-side rate limit exceeded for {0}")]
+    RateLimited(String),
+    #[cfg(feature = "keystore")]
+    #[error("Keystore error: {0}")]
+    Keystore(String),
     #[error("Metadata Error: {0}")]
     Metadata(#[from] node_metadata::MetadataError),
     #[cfg(feature = "ws-client")]
@@ -472,6 +802,71 @@ pub enum ApiClientError {
     Deserializing(#[from] serde_json::Error),
     #[error("UnsupportedXtStatus Error: Can only wait for finalized, in block, broadcast and ready. Waited for: {0:?}")]
     UnsupportedXtStatus(XtStatus),
+    #[error("Waiting for {0:?} needs a subscription, which this client does not support. Use XtStatus::Ready instead")]
+    WatchNotSupported(XtStatus),
+    #[cfg(feature = "http-client")]
+    #[error("Http Error: {0}")]
+    Http(#[from] Box<ureq::Error>),
+    #[cfg(feature = "async")]
+    #[error("WebSocket Error: {0}")]
+    AsyncWebSocket(#[from] Box<tokio_tungstenite::tungstenite::Error>),
     #[error("Error converting NumberOrHex to Balance")]
     TryFromIntError,
+    #[error("Extrinsic was not submitted, its dry run failed: {0:?}")]
+    DryRunFailed(DryRunResult),
+    #[error("The node does not support the RPC method {0}")]
+    MethodNotSupported(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::std::rpc::mock_node::{test_metadata, MockNode};
+    use serde_json::json;
+    use support::weights::DispatchClass;
+
+    #[test]
+    fn unknown_methods_are_reported_as_not_supported() {
+        let not_found = ApiClientError::Rpc {
+            code: METHOD_NOT_FOUND,
+            message: "Method not found".to_string(),
+            data: None,
+        };
+        assert!(matches!(
+            method_not_supported(not_found, "payment_queryInfo"),
+            ApiClientError::MethodNotSupported(method) if method == "payment_queryInfo"
+        ));
+
+        let other = ApiClientError::Rpc {
+            code: -32602,
+            message: "Invalid params".to_string(),
+            data: None,
+        };
+        assert!(matches!(
+            method_not_supported(other, "payment_queryInfo"),
+            ApiClientError::Rpc { code: -32602, .. }
+        ));
+    }
+
+    #[test]
+    fn payment_info_is_decoded_from_the_node_response() {
+        let node = MockNode::new(test_metadata());
+        let api: Api<sr25519::Pair, _> = Api::new(node.clone()).unwrap();
+        node.set_response(
+            "payment_queryInfo",
+            json!({"weight": 195000000, "class": "normal", "partialFee": "15600000001"}),
+        );
+
+        let info = api.get_payment_info("0x00", None).unwrap();
+        assert_eq!(info.weight, 195_000_000);
+        assert_eq!(info.class, DispatchClass::Normal);
+        assert_eq!(info.partial_fee, 15_600_000_001);
+
+        let call = ([1u8, 0], 42u128);
+        assert_eq!(
+            api.estimate_fee(call, 7, Era::mortal(64, 100), None)
+                .unwrap(),
+            15_600_000_001
+        );
+    }
 }
@@ -44,6 +44,8 @@ pub enum MetadataError {
     StorageTypeError,
     #[error("Map value type error")]
     MapValueTypeError,
+    #[error("Storage key can not be decoded: {0}")]
+    StorageKeyDecode(String),
     #[error("Module with errors not found")]
     ModuleWithErrorsNotFound(u8),
     #[error("Error not found")]
@@ -495,9 +497,10 @@ impl StorageMetadata {
             _ => Err(MetadataError::StorageTypeError),
         }
     }
+    /// Prefix of all keys of a map or double map.
     pub fn get_map_prefix(&self) -> Result<StorageKey, MetadataError> {
         match &self.ty {
-            StorageEntryType::Map { .. } => {
+            StorageEntryType::Map { .. } | StorageEntryType::DoubleMap { .. } => {
                 let mut bytes = sp_core::twox_128(&self.module_prefix.as_bytes().to_vec()).to_vec();
                 bytes.extend(&sp_core::twox_128(&self.storage_prefix.as_bytes().to_vec())[..]);
                 Ok(StorageKey(bytes))
@@ -557,6 +560,17 @@ impl<K: Encode, V: Decode + Clone> StorageMap<K, V> {
     }
 }
 
+impl<K: Decode, V> StorageMap<K, V> {
+    /// Recovers the map key from a storage key of this map. Only possible if the hasher keeps the
+    /// key, like `Blake2_128Concat`.
+    pub fn decode_key(&self, key: &StorageKey) -> Result<K, MetadataError> {
+        let mut input = key_without_prefix(key)?;
+        let map_key = key_unhash(&mut input, &self.hasher)?;
+        ensure_consumed(input)?;
+        Ok(map_key)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct StorageDoubleMap<K, Q, V> {
     _marker: PhantomData<K>,
@@ -582,6 +596,18 @@ impl<K: Encode, Q: Encode, V: Decode + Clone> StorageDoubleMap<K, Q, V> {
     }
 }
 
+impl<K: Decode, Q: Decode, V> StorageDoubleMap<K, Q, V> {
+    /// Recovers both map keys from a storage key of this map. Only possible if the hashers keep
+    /// the keys, like `Blake2_128Concat`.
+    pub fn decode_key(&self, key: &StorageKey) -> Result<(K, Q), MetadataError> {
+        let mut input = key_without_prefix(key)?;
+        let key1 = key_unhash(&mut input, &self.hasher)?;
+        let key2 = key_unhash(&mut input, &self.key2_hasher)?;
+        ensure_consumed(input)?;
+        Ok((key1, key2))
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct ModuleConstantMetadata {
     name: String,
@@ -832,6 +858,43 @@ fn convert_entry(
     })
 }
 
+/// Strips the two `twox_128` hashes of the module and storage name.
+fn key_without_prefix(key: &StorageKey) -> Result<&[u8], MetadataError> {
+    key.0
+        .get(32..)
+        .ok_or_else(|| MetadataError::StorageKeyDecode("key is shorter than its prefix".into()))
+}
+
+fn key_unhash<K: Decode>(input: &mut &[u8], hasher: &StorageHasher) -> Result<K, MetadataError> {
+    let hash_len = match hasher {
+        StorageHasher::Identity => 0,
+        StorageHasher::Blake2_128Concat => 16,
+        StorageHasher::Twox64Concat => 8,
+        other => {
+            return Err(MetadataError::StorageKeyDecode(format!(
+                "{:?} can not be reversed",
+                other
+            )))
+        }
+    };
+    if input.len() < hash_len {
+        return Err(MetadataError::StorageKeyDecode("key is too short".into()));
+    }
+    *input = &input[hash_len..];
+    K::decode(input).map_err(|e| MetadataError::StorageKeyDecode(e.to_string()))
+}
+
+fn ensure_consumed(input: &[u8]) -> Result<(), MetadataError> {
+    if input.is_empty() {
+        Ok(())
+    } else {
+        Err(MetadataError::StorageKeyDecode(format!(
+            "{} bytes left after the key",
+            input.len()
+        )))
+    }
+}
+
 /// generates the key's hash depending on the StorageHasher selected
 fn key_hash<K: Encode>(key: &K, hasher: &StorageHasher) -> Vec<u8> {
     let encoded_key = key.encode();
@@ -857,3 +920,36 @@ fn key_hash<K: Encode>(key: &K, hasher: &StorageHasher) -> Vec<u8> {
             .collect(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_map(hasher: StorageHasher) -> StorageMap<u32, u64> {
+        StorageMap {
+            _marker: PhantomData,
+            module_prefix: b"Module".to_vec(),
+            storage_prefix: b"Map".to_vec(),
+            hasher,
+            default: 0,
+        }
+    }
+
+    #[test]
+    fn map_keys_are_recovered_from_concat_hashers() {
+        for hasher in vec![
+            StorageHasher::Blake2_128Concat,
+            StorageHasher::Twox64Concat,
+            StorageHasher::Identity,
+        ] {
+            let map = test_map(hasher);
+            assert_eq!(map.decode_key(&map.key(42)).unwrap(), 42);
+        }
+
+        let map = test_map(StorageHasher::Blake2_256);
+        assert!(matches!(
+            map.decode_key(&map.key(42)),
+            Err(MetadataError::StorageKeyDecode(_))
+        ));
+    }
+}
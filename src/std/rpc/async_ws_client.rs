@@ -0,0 +1,624 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! Asynchronous WebSocket client driven by a tokio task instead of an OS thread.
+
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc::RecvError;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use log::{debug, error, info, warn};
+use serde_json::Value;
+use tokio::sync::{mpsc, oneshot};
+use tokio_tungstenite::tungstenite::{Error as WsError, Message};
+
+use crate::std::async_api::{AsyncRpcClient, AsyncSubscriber};
+use crate::std::rpc::{batch_results, json_req, response_id, response_result, unsubscribe_method};
+use crate::std::{ApiClientError, ApiResult, FromHexString, XtStatus};
+use crate::Hash;
+
+/// Asynchronous JSON-RPC client that sends all requests and subscriptions over one WebSocket
+/// connection.
+///
+/// The connection is driven by a task on the tokio runtime the client was created on. It is
+/// closed once all clones of the client are dropped, which also ends all subscriptions.
+#[derive(Debug, Clone)]
+pub struct AsyncWsRpcClient {
+    commands: mpsc::UnboundedSender<Command>,
+    next_id: Arc<AtomicU32>,
+}
+
+impl AsyncWsRpcClient {
+    /// Connects to `url`. Must be called from within a tokio runtime.
+    pub async fn new(url: &str) -> ApiResult<AsyncWsRpcClient> {
+        let (socket, _) = tokio_tungstenite::connect_async(url)
+            .await
+            .map_err(Box::new)?;
+        info!("websocket connection to {} opened", url);
+
+        let (commands, commands_out) = mpsc::unbounded_channel();
+        let next_id = Arc::new(AtomicU32::new(1));
+        tokio::spawn(run(socket, commands_out, next_id.clone()));
+        Ok(AsyncWsRpcClient { commands, next_id })
+    }
+
+    /// Sends `jsonreq` and returns the whole response.
+    async fn request(&self, jsonreq: Value) -> ApiResult<Value> {
+        let (response, response_out) = oneshot::channel();
+        self.send(jsonreq, response, None)?;
+        response_out.await.map_err(|_| closed())
+    }
+
+    /// Sends `jsonreq` with a fresh id and returns it. The requests of a batch get consecutive
+    /// ids, starting with the returned one.
+
+    fn send(
+        &self,
+        mut jsonreq: Value,
+        response: oneshot::Sender<Value>,
+        subscription: Option<Subscribed>,
+    ) -> ApiResult<u32> {
+        let id = match &mut jsonreq {
+            // a batch is routed by the id of its first request
+            Value::Array(batch) => {
+                let id = self.next_ids(batch.len().max(1) as u32);
+                for (i, req) in batch.iter_mut().enumerate() {
+                    set_id(req, id + i as u32)?;
+                }
+                id
+            }
+            req => {
+                let id = self.next_ids(1);
+                set_id(req, id)?;
+                id
+            }
+        };
+
+        self.commands
+            .send(Command::Request {
+                id,
+                request: jsonreq,
+                response,
+                subscription,
+            })
+            .map(|_| id)
+            .map_err(|_| closed())
+    }
+
+    /// Reserves `count` consecutive ids and returns the first one.
+    fn next_ids(&self, count: u32) -> u32 {
+        self.next_id.fetch_add(count, Ordering::SeqCst)
+    }
+}
+
+#[async_trait]
+impl AsyncRpcClient for AsyncWsRpcClient {
+    async fn get_request(&self, jsonreq: Value) -> ApiResult<String> {
+        let response = self.request(jsonreq).await?;
+        info!("Got get_request response {}", response);
+        response_result(&response)
+    }
+
+    async fn get_batch(&self, jsonreqs: Vec<Value>) -> ApiResult<Vec<Option<String>>> {
+        if jsonreqs.is_empty() {
+            return Ok(Vec::new());
+        }
+        let count = jsonreqs.len() as u32;
+        let (response, response_out) = oneshot::channel();
+        let id = self.send(json_req::batch(jsonreqs), response, None)?;
+        let ids: Vec<u32> = (id..id + count).collect();
+        let response = response_out.await.map_err(|_| closed())?;
+        info!("Got batch response {}", response);
+        batch_results(response, &ids)
+    }
+
+    async fn send_extrinsic(
+        &self,
+        xthex_prefixed: String,
+        exit_on: XtStatus,
+    ) -> ApiResult<Option<Hash>> {
+        if status_rank(&exit_on) == 0 {
+            return Err(ApiClientError::UnsupportedXtStatus(exit_on));
+        }
+
+        let jsonreq = json_req::author_submit_and_watch_extrinsic(&xthex_prefixed);
+        let mut updates = self.subscribe(jsonreq).await?;
+        while let Some(update) = updates.next().await {
+            debug!("extrinsic update: {}", update);
+            match extrinsic_status(&update)? {
+                (XtStatus::Future, _) => {
+                    warn!("extrinsic has 'future' status. aborting");
+                    return Ok(None);
+                }
+                (XtStatus::Error, _) => {
+                    return Err(ApiClientError::RpcClient(format!(
+                        "extrinsic failed: {}",
+                        update
+                    )))
+                }
+                (status, hash) if status_rank(&status) >= status_rank(&exit_on) => {
+                    info!("{:?}: {:?}", status, hash);
+                    return Ok(watched_hash(&exit_on, hash));
+                }
+                _ => {}
+            }
+        }
+        Err(closed())
+    }
+}
+
+#[async_trait]
+impl AsyncSubscriber for AsyncWsRpcClient {
+    async fn subscribe(&self, jsonreq: Value) -> ApiResult<BoxStream<'static, Value>> {
+        let (response, response_out) = oneshot::channel();
+        let (notifications, notifications_out) = mpsc::unbounded_channel();
+        let subscribed = Subscribed {
+            notifications,
+            unsubscribe_method: jsonreq["method"].as_str().and_then(unsubscribe_method),
+        };
+        self.send(jsonreq, response, Some(subscribed))?;
+
+        let response = response_out.await.map_err(|_| closed())?;
+        response_result(&response)?;
+        Ok(Subscription {
+            id: response["result"].clone(),
+            notifications: notifications_out,
+            commands: self.commands.clone(),
+        }
+        .boxed())
+    }
+}
+
+/// Messages from the clients to the task driving the connection.
+#[derive(Debug)]
+enum Command {
+    Request {
+        id: u32,
+        request: Value,
+        response: oneshot::Sender<Value>,
+        subscription: Option<Subscribed>,
+    },
+    Unsubscribe {
+        subscription: Value,
+    },
+}
+
+#[derive(Debug)]
+struct Pending {
+    response: oneshot::Sender<Value>,
+    subscription: Option<Subscribed>,
+    /// Whether the request is a batch.
+    batch: bool,
+}
+
+#[derive(Debug)]
+struct Subscribed {
+    notifications: mpsc::UnboundedSender<Value>,
+    /// Method to cancel the subscription with.
+    unsubscribe_method: Option<&'static str>,
+}
+
+/// Stream of the notifications of a subscription. Dropping it cancels the subscription.
+struct Subscription {
+    id: Value,
+    notifications: mpsc::UnboundedReceiver<Value>,
+    commands: mpsc::UnboundedSender<Command>,
+}
+
+impl Stream for Subscription {
+    type Item = Value;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Value>> {
+        self.notifications.poll_recv(cx)
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let subscription = self.id.clone();
+        // the connection may already be closed, nothing to cancel then
+        let _ = self.commands.send(Command::Unsubscribe { subscription });
+    }
+}
+
+/// Keeps track of which messages belong to which request.
+#[derive(Debug)]
+struct Router {
+    /// Requests waiting for their response, by JSON-RPC id.
+    pending: HashMap<u32, Pending>,
+    /// Active subscriptions, by the subscription id returned by the node.
+    subscriptions: HashMap<String, Subscribed>,
+    next_id: Arc<AtomicU32>,
+}
+
+impl Router {
+    fn new(next_id: Arc<AtomicU32>) -> Self {
+        Self {
+            pending: HashMap::new(),
+            subscriptions: HashMap::new(),
+            next_id,
+        }
+    }
+
+    /// Returns the message to send to the node, if any.
+    fn command(&mut self, command: Command) -> Option<String> {
+        match command {
+            Command::Request {
+                id,
+                request,
+                response,
+                subscription,
+            } => {
+                self.pending.insert(
+                    id,
+                    Pending {
+                        response,
+                        subscription,
+                        batch: request.is_array(),
+                    },
+                );
+                info!("sending request: {}", request);
+                Some(request.to_string())
+            }
+            Command::Unsubscribe { subscription } => self.unsubscribe(&subscription),
+        }
+    }
+
+    /// Routes `msg` to the request it belongs to. Returns the message to send to the node, if
+    /// any.
+    fn message(&mut self, msg: &str) -> Option<String> {
+        let value: Value = match serde_json::from_str(msg) {
+            Ok(v) => v,
+            Err(e) => {
+                error!("could not parse message {}: {}", msg, e);
+                return None;
+            }
+        };
+
+        if let Some(id) = response_id(&value).or_else(|| self.rejected_batch(&value)) {
+            match self.pending.remove(&id) {
+                Some(pending) => {
+                    let subscription = &value["result"];
+                    if let Some(subscribed) = pending.subscription {
+                        if value["error"].is_null() && !subscription.is_null() {
+                            self.subscriptions
+                                .insert(subscription.to_string(), subscribed);
+                        }
+                    }
+                    if pending.response.send(value).is_err() {
+                        debug!("response with id {} is not awaited anymore", id);
+                    }
+                }
+                None => debug!("no pending request for response with id {}", id),
+            }
+            return None;
+        }
+
+        let subscription = &value["params"]["subscription"];
+        let closed = match self.subscriptions.get(&subscription.to_string()) {
+            Some(subscribed) => subscribed
+                .notifications
+                .send(value["params"]["result"].clone())
+                .is_err(),
+            None => {
+                warn!("received message without a matching request: {}", msg);
+                return None;
+            }
+        };
+        if closed {
+            self.unsubscribe(subscription)
+        } else {
+            None
+        }
+    }
+
+    /// The node answers a batch it rejects as a whole with a single error without id. It is
+    /// attributed to the oldest pending batch.
+    fn rejected_batch(&self, value: &Value) -> Option<u32> {
+        if !value.is_object() || !value["id"].is_null() || value["error"].is_null() {
+            return None;
+        }
+        self.pending
+            .iter()
+            .filter(|(_, pending)| pending.batch)
+            .map(|(id, _)| *id)
+            .min()
+    }
+
+    fn unsubscribe(&mut self, subscription: &Value) -> Option<String> {
+        let subscribed = self.subscriptions.remove(&subscription.to_string())?;
+        let method = subscribed.unsubscribe_method?;
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let jsonreq = json_req::unsubscribe_with_id(method, subscription.clone(), id);
+        debug!("cancelling subscription: {}", jsonreq);
+        Some(jsonreq.to_string())
+    }
+}
+
+/// Drives the connection until it is closed or all clients are dropped.
+async fn run<S>(
+    mut socket: S,
+    mut commands: mpsc::UnboundedReceiver<Command>,
+    next_id: Arc<AtomicU32>,
+) where
+    S: Stream<Item = Result<Message, WsError>> + Sink<Message, Error = WsError> + Unpin,
+{
+    let mut router = Router::new(next_id);
+    loop {
+        let outgoing = tokio::select! {
+            command = commands.recv() => match command {
+                Some(command) => router.command(command),
+                None => break,
+            },
+            msg = socket.next() => match msg {
+                Some(Ok(Message::Text(msg))) => router.message(&msg),
+                Some(Ok(Message::Close(_))) | None => break,
+                Some(Ok(_)) => None,
+                Some(Err(e)) => {
+                    error!("websocket error: {}", e);
+                    break;
+                }
+            },
+        };
+        if let Some(msg) = outgoing {
+            if let Err(e) = socket.send(Message::Text(msg)).await {
+                error!("could not send message: {}", e);
+                break;
+            }
+        }
+    }
+    // dropping the router disconnects all requests and subscriptions that are still waiting
+    info!("websocket connection closed");
+    let _ = socket.close().await;
+}
+
+fn set_id(req: &mut Value, id: u32) -> ApiResult<()> {
+    match req.as_object_mut() {
+        Some(obj) => {
+            obj.insert("id".to_string(), Value::String(id.to_string()));
+            Ok(())
+        }
+        None => Err(ApiClientError::RpcClient(
+            "JSON-RPC request must be an object".to_string(),
+        )),
+    }
+}
+
+fn closed() -> ApiClientError {
+    ApiClientError::Disconnected(RecvError)
+}
+
+/// Parses an `author_extrinsicUpdate` notification.
+fn extrinsic_status(update: &Value) -> ApiResult<(XtStatus, Option<Hash>)> {
+    let status = match update {
+        Value::String(status) => match status.as_str() {
+            "ready" => XtStatus::Ready,
+            "future" => XtStatus::Future,
+            "dropped" | "invalid" => XtStatus::Error,
+            _ => XtStatus::Unknown,
+        },
+        Value::Object(obj) => {
+            if let Some(hash) = obj.get("finalized") {
+                return Ok((XtStatus::Finalized, Some(Hash::from_hex(hash.to_string())?)));
+            } else if let Some(hash) = obj.get("inBlock") {
+                return Ok((XtStatus::InBlock, Some(Hash::from_hex(hash.to_string())?)));
+            } else if obj.contains_key("broadcast") {
+                XtStatus::Broadcast
+            } else if obj.contains_key("usurped") {
+                XtStatus::Error
+            } else {
+                XtStatus::Unknown
+            }
+        }
+        _ => XtStatus::Unknown,
+    };
+    Ok((status, None))
+}
+
+/// Like in the sync client, only waiting for `InBlock` or `Finalized` returns the block hash,
+/// also if the watch stopped on a later status than `exit_on`.
+fn watched_hash(exit_on: &XtStatus, hash: Option<Hash>) -> Option<Hash> {
+    match exit_on {
+        XtStatus::InBlock | XtStatus::Finalized => hash,
+        _ => None,
+    }
+}
+
+/// Orders the statuses that can be waited for. `0` for all others.
+fn status_rank(status: &XtStatus) -> u8 {
+    match status {
+        XtStatus::Ready => 1,
+        XtStatus::Broadcast => 2,
+        XtStatus::InBlock => 3,
+        XtStatus::Finalized => 4,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request(router: &mut Router, id: u32, subscribe: bool) -> TestRequest {
+        let (response, response_out) = oneshot::channel();
+        let (notifications, notifications_out) = mpsc::unbounded_channel();
+        let subscription = if subscribe {
+            Some(Subscribed {
+                notifications,
+                unsubscribe_method: unsubscribe_method("chain_subscribeFinalizedHeads"),
+            })
+        } else {
+            None
+        };
+        let sent = router.command(Command::Request {
+            id,
+            request: json!({"jsonrpc": "2.0", "method": "test", "id": id.to_string()}),
+            response,
+            subscription,
+        });
+        assert!(sent.is_some());
+        TestRequest {
+            response: response_out,
+            notifications: notifications_out,
+        }
+    }
+
+    struct TestRequest {
+        response: oneshot::Receiver<Value>,
+        notifications: mpsc::UnboundedReceiver<Value>,
+    }
+
+    #[test]
+    fn responses_are_routed_by_id() {
+        let mut router = Router::new(Arc::new(AtomicU32::new(10)));
+        let mut first = request(&mut router, 1, false);
+        let mut second = request(&mut router, 2, false);
+
+        router.message(r#"{"jsonrpc":"2.0","result":"0x02","id":"2"}"#);
+        router.message(r#"{"jsonrpc":"2.0","result":"0x01","id":"1"}"#);
+
+        assert_eq!(first.response.try_recv().unwrap()["result"], "0x01");
+        assert_eq!(second.response.try_recv().unwrap()["result"], "0x02");
+        assert!(router.pending.is_empty());
+    }
+
+    #[test]
+    fn notifications_are_routed_by_subscription() {
+        let mut router = Router::new(Arc::new(AtomicU32::new(10)));
+        let mut sub = request(&mut router, 1, true);
+
+        router.message(r#"{"jsonrpc":"2.0","result":"abc","id":"1"}"#);
+        assert_eq!(sub.response.try_recv().unwrap()["result"], "abc");
+
+        let notification = r#"{"jsonrpc":"2.0","method":"chain_finalizedHead","params":{"result":{"number":"0x1"},"subscription":"abc"}}"#;
+        assert_eq!(router.message(notification), None);
+        assert_eq!(
+            sub.notifications.blocking_recv().unwrap(),
+            json!({"number": "0x1"})
+        );
+
+        // the consumer is gone, so the subscription is cancelled
+        drop(sub);
+        let unsubscribe: Value =
+            serde_json::from_str(&router.message(notification).unwrap()).unwrap();
+        assert_eq!(unsubscribe["method"], "chain_unsubscribeFinalizedHeads");
+        assert_eq!(unsubscribe["params"], json!(["abc"]));
+        assert_eq!(unsubscribe["id"], "10");
+        assert!(router.subscriptions.is_empty());
+    }
+
+    #[test]
+    fn failed_subscriptions_are_not_registered() {
+        let mut router = Router::new(Arc::new(AtomicU32::new(10)));
+        let mut sub = request(&mut router, 1, true);
+
+        router.message(
+            r#"{"jsonrpc":"2.0","error":{"code":-32601,"message":"Method not found"},"id":"1"}"#,
+        );
+        assert!(response_result(&sub.response.try_recv().unwrap()).is_err());
+        assert!(router.subscriptions.is_empty());
+    }
+
+    #[test]
+    fn rejected_batches_get_the_error() {
+        let mut router = Router::new(Arc::new(AtomicU32::new(10)));
+        let _single = request(&mut router, 1, false);
+        let (response, mut response_out) = oneshot::channel();
+        router.command(Command::Request {
+            id: 2,
+            request: json!([{"jsonrpc": "2.0", "method": "test", "id": "2"}]),
+            response,
+            subscription: None,
+        });
+
+        router.message(
+            r#"{"jsonrpc":"2.0","error":{"code":-32600,"message":"Invalid request"},"id":null}"#,
+        );
+        assert!(response_result(&response_out.try_recv().unwrap()).is_err());
+        assert!(router.pending.contains_key(&1));
+    }
+
+    #[test]
+    fn unsubscribe_command_cancels_subscription() {
+        let mut router = Router::new(Arc::new(AtomicU32::new(10)));
+        let _sub = request(&mut router, 1, true);
+        router.message(r#"{"jsonrpc":"2.0","result":"abc","id":"1"}"#);
+
+        let unsubscribe = router.command(Command::Unsubscribe {
+            subscription: json!("abc"),
+        });
+        assert!(unsubscribe
+            .unwrap()
+            .contains("chain_unsubscribeFinalizedHeads"));
+        assert_eq!(
+            router.command(Command::Unsubscribe {
+                subscription: json!("abc"),
+            }),
+            None
+        );
+    }
+
+    #[test]
+    fn extrinsic_status_parsed_correctly() {
+        let hash = "0x4d6d7c3c8a4b1ac4b5e0a9f2d8fd9d1f9c3b8b0e7e9bb9a6b7f5b3c2a1d0e9f8";
+        assert_eq!(
+            extrinsic_status(&json!("ready")).unwrap(),
+            (XtStatus::Ready, None)
+        );
+        assert_eq!(
+            extrinsic_status(
+                &json!({"broadcast": ["QmfSF4VYWNqNf5KYHpDEdY8Rt1nPUgSkMweDkYzhSWirGY"]})
+            )
+            .unwrap(),
+            (XtStatus::Broadcast, None)
+        );
+        assert_eq!(
+            extrinsic_status(&json!({ "inBlock": hash })).unwrap(),
+            (
+                XtStatus::InBlock,
+                Some(Hash::from_hex(hash.to_string()).unwrap())
+            )
+        );
+        assert_eq!(
+            extrinsic_status(&json!({ "finalized": hash })).unwrap(),
+            (
+                XtStatus::Finalized,
+                Some(Hash::from_hex(hash.to_string()).unwrap())
+            )
+        );
+        assert_eq!(
+            extrinsic_status(&json!("invalid")).unwrap(),
+            (XtStatus::Error, None)
+        );
+    }
+
+    #[test]
+    fn only_block_statuses_return_the_block_hash() {
+        let hash = Some(Hash::repeat_byte(1));
+        assert_eq!(watched_hash(&XtStatus::Ready, hash), None);
+        assert_eq!(watched_hash(&XtStatus::Broadcast, hash), None);
+        assert_eq!(watched_hash(&XtStatus::InBlock, hash), hash);
+        assert_eq!(watched_hash(&XtStatus::Finalized, hash), hash);
+    }
+}
@@ -0,0 +1,119 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! Types of the `author_*` RPCs that manage the transaction pool.
+
+use serde::{Deserialize, Serialize};
+use sp_core::{blake2_256, Bytes};
+
+use crate::{Hash, UncheckedExtrinsicV4};
+
+/// Selects an extrinsic of the transaction pool for `author_removeExtrinsic`.
+///
+/// The node does not support selecting extrinsics by their tags.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExtrinsicOrHash {
+    Hash(Hash),
+    /// The encoded extrinsic.
+    Extrinsic(Bytes),
+}
+
+/// An extrinsic waiting in the transaction pool.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingExtrinsic {
+    /// Hash the pool knows the extrinsic by.
+    pub hash: Hash,
+    /// The extrinsic, with the encoded call as `function`.
+    pub extrinsic: UncheckedExtrinsicV4<Vec<u8>>,
+}
+
+impl PendingExtrinsic {
+    pub fn decode(encoded: &[u8]) -> Result<Self, codec::Error> {
+        Ok(Self {
+            hash: blake2_256(encoded).into(),
+            extrinsic: UncheckedExtrinsicV4::decode_opaque(encoded)?,
+        })
+    }
+}
+
+/// Nonces of the signer's extrinsics in the transaction pool.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PoolNonces {
+    /// Nonce of the signer's account on chain.
+    pub account_nonce: u32,
+    /// Nonces that follow the account nonce without gap and can be included.
+    pub ready: Vec<u32>,
+    /// Nonces behind a gap. These stay in the pool until the missing nonces are submitted.
+    pub stuck: Vec<u32>,
+    /// Nonces between the ready and the stuck ones that are not in the pool.
+    pub missing: Vec<u32>,
+}
+
+impl PoolNonces {
+    pub fn new(account_nonce: u32, mut pool: Vec<u32>) -> Self {
+        pool.sort_unstable();
+        pool.dedup();
+
+        let mut next = account_nonce;
+        let mut nonces = Self {
+            account_nonce,
+            ..Default::default()
+        };
+        // nonces below the account nonce are outdated and will be dropped by the pool
+        for nonce in pool.into_iter().filter(|n| *n >= account_nonce) {
+            if nonce == next && nonces.stuck.is_empty() {
+                nonces.ready.push(nonce);
+            } else {
+                nonces.missing.extend(next..nonce);
+                nonces.stuck.push(nonce);
+            }
+            next = nonce.saturating_add(1);
+        }
+        nonces
+    }
+
+    pub fn has_stuck(&self) -> bool {
+        !self.stuck.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nonces_behind_a_gap_are_stuck() {
+        let nonces = PoolNonces::new(5, vec![8, 5, 6, 10, 3]);
+        assert_eq!(nonces.ready, vec![5, 6]);
+        assert_eq!(nonces.stuck, vec![8, 10]);
+        assert_eq!(nonces.missing, vec![7, 9]);
+
+        let nonces = PoolNonces::new(5, vec![5, 6]);
+        assert!(!nonces.has_stuck());
+        assert!(nonces.missing.is_empty());
+    }
+
+    #[test]
+    fn extrinsic_or_hash_matches_the_node_format() {
+        let hash = ExtrinsicOrHash::Hash(Hash::repeat_byte(1));
+        assert_eq!(
+            serde_json::to_value(hash).unwrap(),
+            serde_json::json!({ "hash": format!("0x{}", "01".repeat(32)) })
+        );
+    }
+}
@@ -0,0 +1,114 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! Timeouts and cancellation for calls that block on a channel.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, RecvError, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::std::{ApiClientError, ApiResult};
+
+/// How often a cancellable receive checks whether it got cancelled.
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Cancels blocking calls from another thread. Clones share the same state.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Returns the point in time a call with `timeout` has to be finished at.
+pub fn deadline(timeout: Option<Duration>) -> Option<Instant> {
+    timeout.map(|t| Instant::now() + t)
+}
+
+/// Receives the next message, giving up at `deadline` or once `cancel` has been cancelled.
+pub fn recv<T>(
+    receiver: &Receiver<T>,
+    deadline: Option<Instant>,
+    cancel: Option<&CancelToken>,
+) -> ApiResult<T> {
+    loop {
+        if cancel.map_or(false, |c| c.is_cancelled()) {
+            return Err(ApiClientError::Cancelled);
+        }
+
+        let mut wait = match deadline {
+            Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                Some(wait) if !wait.is_zero() => Some(wait),
+                _ => return Err(ApiClientError::Timeout),
+            },
+            None => None,
+        };
+        if cancel.is_some() {
+            wait = Some(wait.map_or(CANCEL_POLL_INTERVAL, |w| w.min(CANCEL_POLL_INTERVAL)));
+        }
+
+        return match wait {
+            Some(wait) => match receiver.recv_timeout(wait) {
+                Ok(msg) => Ok(msg),
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => Err(ApiClientError::Disconnected(RecvError)),
+            },
+            None => receiver.recv().map_err(|e| e.into()),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn recv_times_out() {
+        let (_sender, receiver) = channel::<String>();
+        let res = recv(&receiver, deadline(Some(Duration::from_millis(10))), None);
+        assert!(matches!(res, Err(ApiClientError::Timeout)));
+    }
+
+    #[test]
+    fn recv_is_cancelled() {
+        let (_sender, receiver) = channel::<String>();
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        let res = recv(&receiver, None, Some(&cancel));
+        assert!(matches!(res, Err(ApiClientError::Cancelled)));
+    }
+
+    #[test]
+    fn recv_returns_message_before_deadline() {
+        let (sender, receiver) = channel();
+        sender.send("msg".to_string()).unwrap();
+        let res = recv(&receiver, deadline(Some(Duration::from_secs(1))), None);
+        assert_eq!(res.unwrap(), "msg");
+    }
+}
@@ -0,0 +1,281 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use log::{debug, warn};
+use serde_json::Value;
+
+#[cfg(feature = "ws-client")]
+use std::sync::mpsc::Sender as ThreadOut;
+
+#[cfg(feature = "ws-client")]
+use crate::std::rpc::ws_client::{Reconnected, Subscriber};
+use crate::std::rpc::{is_idempotent, json_req};
+use crate::std::{ApiClientError, ApiResult, RpcClient, XtStatus};
+use crate::Hash;
+
+/// Routes requests to one of several endpoints and fails over to the next healthy one on
+/// transport errors.
+///
+/// An endpoint is healthy if it answers `system_health` and is not syncing. Extrinsics and
+/// subscriptions are never retried on another endpoint, so a watch stays on the node the
+/// extrinsic was submitted to. Requests that change the node, like `author_rotateKeys`, only
+/// fail over if they could not be sent at all, otherwise they might run on two nodes.
+#[derive(Debug, Clone)]
+pub struct FailoverRpcClient<Client> {
+    clients: Vec<Client>,
+    current: Arc<AtomicUsize>,
+}
+
+impl<Client: RpcClient> FailoverRpcClient<Client> {
+    pub fn new(clients: Vec<Client>) -> Self {
+        Self {
+            clients,
+            current: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Index of the endpoint requests are currently sent to.
+    pub fn current(&self) -> usize {
+        self.current.load(Ordering::SeqCst)
+    }
+
+    /// Returns the health of every endpoint, in the order they were given.
+    pub fn check_health(&self) -> Vec<bool> {
+        self.clients.iter().map(is_healthy).collect()
+    }
+
+    /// Returns the index of the first healthy endpoint, starting at the current one.
+    fn healthy_index(&self) -> ApiResult<usize> {
+        let start = self.current();
+        let len = self.clients.len();
+        let index = (0..len)
+            .map(|i| (start + i) % len)
+            .find(|&index| is_healthy(&self.clients[index]))
+            .ok_or(ApiClientError::NoHealthyEndpoint)?;
+        self.current.store(index, Ordering::SeqCst);
+        Ok(index)
+    }
+
+    /// Calls `f` on the current endpoint and on the next healthy ones as long as it fails with
+    /// a transport error. Unless the requests of `f` are idempotent, only as long as they were
+    /// not sent.
+    fn with_failover<T, F>(&self, idempotent: bool, f: F) -> ApiResult<T>
+    where
+        F: Fn(&Client) -> ApiResult<T>,
+    {
+        let start = self.current();
+        let len = self.clients.len();
+        let mut last_error = None;
+
+        for index in (0..len).map(|i| (start + i) % len) {
+            let client = &self.clients[index];
+            // the current endpoint was healthy last time, the others need to prove it
+            if index != start && !is_healthy(client) {
+                debug!("skipping unhealthy endpoint {}", index);
+                continue;
+            }
+            match f(client) {
+                Err(e) if e.is_transport_error() && (idempotent || e.is_unsent()) => {
+                    warn!("endpoint {} failed: {}. failing over", index, e);
+                    last_error = Some(e);
+                }
+                res => {
+                    self.current.store(index, Ordering::SeqCst);
+                    return res;
+                }
+            }
+        }
+        Err(last_error.unwrap_or(ApiClientError::NoHealthyEndpoint))
+    }
+}
+
+impl<Client: RpcClient> RpcClient for FailoverRpcClient<Client> {
+    fn get_request(&self, jsonreq: Value) -> ApiResult<String> {
+        let idempotent = jsonreq["method"].as_str().map_or(true, is_idempotent);
+        self.with_failover(idempotent, |client| client.get_request(jsonreq.clone()))
+    }
+
+    fn get_batch(&self, jsonreqs: Vec<Value>) -> ApiResult<Vec<Option<String>>> {
+        let idempotent = jsonreqs
+            .iter()
+            .all(|r| r["method"].as_str().map_or(true, is_idempotent));
+        self.with_failover(idempotent, |client| client.get_batch(jsonreqs.clone()))
+    }
+
+    fn send_extrinsic(&self, xthex_prefixed: String, exit_on: XtStatus) -> ApiResult<Option<Hash>> {
+        self.watch_extrinsic(xthex_prefixed, exit_on, &mut |_| {})
+    }
+
+    fn watch_extrinsic(
+        &self,
+        xthex_prefixed: String,
+        exit_on: XtStatus,
+        on_status: &mut dyn FnMut(&Value),
+    ) -> ApiResult<Option<Hash>> {
+        let index = self.healthy_index()?;
+        self.clients[index].watch_extrinsic(xthex_prefixed, exit_on, on_status)
+    }
+}
+
+#[cfg(feature = "ws-client")]
+impl<Client: RpcClient + Subscriber> Subscriber for FailoverRpcClient<Client> {
+    fn start_subscriber(&self, json_req: String, result_in: ThreadOut<String>) -> ApiResult<()> {
+        let index = self.healthy_index()?;
+        self.clients[index].start_subscriber(json_req, result_in)
+    }
+
+    fn start_resumable_subscriber(
+        &self,
+        json_req: String,
+        result_in: ThreadOut<String>,
+        reconnected_in: ThreadOut<Reconnected>,
+    ) -> ApiResult<()> {
+        let index = self.healthy_index()?;
+        self.clients[index].start_resumable_subscriber(json_req, result_in, reconnected_in)
+    }
+}
+
+fn is_healthy<Client: RpcClient>(client: &Client) -> bool {
+    match client.get_request(json_req::system_health()) {
+        Ok(health) => serde_json::from_str::<Value>(&health)
+            .map(|h| h["isSyncing"] == Value::Bool(false))
+            .unwrap_or(false),
+        Err(e) => {
+            debug!("health check failed: {}", e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    struct TestClient {
+        up: bool,
+        syncing: bool,
+        /// Answers `system_health`, but lets all other requests time out.
+        hanging: bool,
+        requests: Cell<u32>,
+    }
+
+    impl TestClient {
+        fn new(up: bool, syncing: bool) -> Self {
+            Self {
+                up,
+                syncing,
+                hanging: false,
+                requests: Cell::new(0),
+            }
+        }
+
+        fn hanging() -> Self {
+            Self {
+                hanging: true,
+                ..Self::new(true, false)
+            }
+        }
+    }
+
+    impl RpcClient for TestClient {
+        fn get_request(&self, jsonreq: Value) -> ApiResult<String> {
+            if !self.up {
+                return Err(ApiClientError::Timeout);
+            }
+            if jsonreq["method"] == "system_health" {
+                return Ok(format!(
+                    r#"{{"peers":1,"isSyncing":{},"shouldHavePeers":true}}"#,
+                    self.syncing
+                ));
+            }
+            self.requests.set(self.requests.get() + 1);
+            if self.hanging {
+                return Err(ApiClientError::Timeout);
+            }
+            Ok("\"0x01\"".to_string())
+        }
+
+        fn send_extrinsic(&self, _: String, _: XtStatus) -> ApiResult<Option<Hash>> {
+            Ok(None)
+        }
+    }
+
+    #[test]
+    fn fails_over_to_next_healthy_endpoint() {
+        let client = FailoverRpcClient::new(vec![
+            TestClient::new(false, false),
+            TestClient::new(true, true),
+            TestClient::new(true, false),
+        ]);
+
+        assert_eq!(
+            client
+                .get_request(json_req::chain_get_finalized_head())
+                .unwrap(),
+            "\"0x01\""
+        );
+        assert_eq!(client.current(), 2);
+        assert_eq!(client.clients[1].requests.get(), 0);
+        assert_eq!(client.clients[2].requests.get(), 1);
+    }
+
+    #[test]
+    fn timed_out_writes_do_not_fail_over() {
+        let client =
+            FailoverRpcClient::new(vec![TestClient::hanging(), TestClient::new(true, false)]);
+
+        assert!(matches!(
+            client.get_request(json_req::author_rotate_keys()),
+            Err(ApiClientError::Timeout)
+        ));
+        assert_eq!(client.clients[0].requests.get(), 1);
+        assert_eq!(client.clients[1].requests.get(), 0);
+
+        client
+            .get_request(json_req::chain_get_finalized_head())
+            .unwrap();
+        assert_eq!(client.current(), 1);
+    }
+
+    #[test]
+    fn fails_without_healthy_endpoint() {
+        let client = FailoverRpcClient::new(vec![TestClient::new(false, false)]);
+
+        assert!(matches!(
+            client.get_request(json_req::chain_get_finalized_head()),
+            Err(ApiClientError::Timeout)
+        ));
+        assert!(matches!(
+            client.send_extrinsic("0x00".to_string(), XtStatus::Ready),
+            Err(ApiClientError::NoHealthyEndpoint)
+        ));
+    }
+
+    #[test]
+    fn health_is_reported_per_endpoint() {
+        let client = FailoverRpcClient::new(vec![
+            TestClient::new(true, false),
+            TestClient::new(true, true),
+            TestClient::new(false, false),
+        ]);
+        assert_eq!(client.check_health(), vec![true, false, false]);
+    }
+}
@@ -0,0 +1,96 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+use std::time::Duration;
+
+use log::{debug, info};
+use serde_json::Value;
+use sp_core::H256 as Hash;
+
+use crate::std::rpc::{batch_results, json_req, response_result};
+use crate::std::{ApiClientError, ApiResult, FromHexString, RpcClient, XtStatus};
+
+/// JSON-RPC client that sends every request as an HTTP POST.
+///
+/// HTTP does not support subscriptions. Extrinsics are submitted with `author_submitExtrinsic`,
+/// so `send_extrinsic` can only wait for `XtStatus::Ready`.
+#[derive(Debug, Clone)]
+pub struct HttpRpcClient {
+    url: String,
+    agent: ureq::Agent,
+}
+
+impl HttpRpcClient {
+    pub fn new(url: &str) -> HttpRpcClient {
+        HttpRpcClient {
+            url: url.to_string(),
+            agent: ureq::agent(),
+        }
+    }
+
+    /// Sets the timeout for all requests of this client.
+    pub fn set_timeout(mut self, timeout: Duration) -> Self {
+        self.agent = ureq::AgentBuilder::new().timeout(timeout).build();
+        self
+    }
+
+    fn send(&self, jsonreq: Value) -> ApiResult<Value> {
+        debug!("sending request: {}", jsonreq);
+        self.agent
+            .post(&self.url)
+            .send_json(jsonreq)
+            .map_err(Box::new)?
+            .into_json()
+            .map_err(|e| ApiClientError::RpcClient(e.to_string()))
+    }
+}
+
+impl RpcClient for HttpRpcClient {
+    fn get_request(&self, jsonreq: Value) -> ApiResult<String> {
+        let response = self.send(jsonreq)?;
+        info!("Got get_request response {}", response);
+        response_result(&response)
+    }
+
+    fn get_batch(&self, jsonreqs: Vec<Value>) -> ApiResult<Vec<Option<String>>> {
+        if jsonreqs.is_empty() {
+            return Ok(Vec::new());
+        }
+        // `json_req::batch` numbers the requests by their position
+        let ids: Vec<u32> = (0..jsonreqs.len() as u32).collect();
+        let response = self.send(json_req::batch(jsonreqs))?;
+        info!("Got batch response {}", response);
+        batch_results(response, &ids)
+    }
+
+    /// Submits the extrinsic and returns its hash once the node accepted it into the pool.
+    fn send_extrinsic(&self, xthex_prefixed: String, exit_on: XtStatus) -> ApiResult<Option<Hash>> {
+        match exit_on {
+            XtStatus::Ready => {}
+            XtStatus::Finalized | XtStatus::InBlock | XtStatus::Broadcast => {
+                return Err(ApiClientError::WatchNotSupported(exit_on))
+            }
+            _ => return Err(ApiClientError::UnsupportedXtStatus(exit_on)),
+        }
+
+        let jsonreq = json_req::author_submit_extrinsic(&xthex_prefixed);
+        let response = self.send(jsonreq)?;
+        let hash = response_result(&response)?;
+        info!("ready: {}", hash);
+        Ok(Some(Hash::from_hex(hash)?))
+    }
+}
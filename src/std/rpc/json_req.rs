@@ -15,10 +15,13 @@
 
 */
 
+use crate::std::rpc::author::ExtrinsicOrHash;
+use crate::std::rpc::offchain::StorageKind;
 use crate::Hash;
 use serde::Serialize;
 use serde_json::{json, to_value, Value};
-use sp_core::storage::StorageKey;
+use sp_core::storage::{PrefixedStorageKey, StorageKey};
+use sp_core::Bytes;
 
 pub const REQUEST_TRANSFER: u32 = 3;
 
@@ -50,6 +53,17 @@ pub fn chain_subscribe_finalized_heads() -> Value {
     json_req("chain_subscribeFinalizedHeads", Value::Null, 1)
 }
 
+pub fn payment_query_info(xthex_prefixed: &str, at_block: Option<Hash>) -> Value {
+    json_req(
+        "payment_queryInfo",
+        vec![
+            to_value(xthex_prefixed).unwrap(),
+            to_value(at_block).unwrap(),
+        ],
+        1,
+    )
+}
+
 pub fn payment_query_fee_details(xthex_prefixed: &str, at_block: Option<Hash>) -> Value {
     json_req(
         "payment_queryFeeDetails",
@@ -61,6 +75,54 @@ pub fn payment_query_fee_details(xthex_prefixed: &str, at_block: Option<Hash>) -
     )
 }
 
+pub fn rpc_methods() -> Value {
+    json_req("rpc_methods", Value::Null, 1)
+}
+
+pub fn system_name() -> Value {
+    json_req("system_name", Value::Null, 1)
+}
+
+pub fn system_version() -> Value {
+    json_req("system_version", Value::Null, 1)
+}
+
+pub fn system_chain() -> Value {
+    json_req("system_chain", Value::Null, 1)
+}
+
+pub fn system_chain_type() -> Value {
+    json_req("system_chainType", Value::Null, 1)
+}
+
+pub fn system_properties() -> Value {
+    json_req("system_properties", Value::Null, 1)
+}
+
+pub fn system_health() -> Value {
+    json_req("system_health", Value::Null, 1)
+}
+
+pub fn system_peers() -> Value {
+    json_req("system_peers", Value::Null, 1)
+}
+
+pub fn system_sync_state() -> Value {
+    json_req("system_syncState", Value::Null, 1)
+}
+
+pub fn system_local_peer_id() -> Value {
+    json_req("system_localPeerId", Value::Null, 1)
+}
+
+pub fn system_dry_run(extrinsic: Bytes, at_block: Option<Hash>) -> Value {
+    json_req(
+        "system_dryRun",
+        vec![to_value(extrinsic).unwrap(), to_value(at_block).unwrap()],
+        1,
+    )
+}
+
 pub fn state_get_metadata() -> Value {
     state_get_metadata_with_id(1)
 }
@@ -110,6 +172,38 @@ pub fn state_get_read_proof(keys: Vec<StorageKey>, at_block: Option<Hash>) -> Va
     )
 }
 
+pub fn state_call(method: &str, data: Bytes, at_block: Option<Hash>) -> Value {
+    json_req(
+        "state_call",
+        vec![
+            to_value(method).unwrap(),
+            to_value(data).unwrap(),
+            to_value(at_block).unwrap(),
+        ],
+        1,
+    )
+}
+
+pub fn state_query_storage(keys: Vec<StorageKey>, from: Hash, to: Option<Hash>) -> Value {
+    json_req(
+        "state_queryStorage",
+        vec![
+            to_value(keys).unwrap(),
+            to_value(from).unwrap(),
+            to_value(to).unwrap(),
+        ],
+        1,
+    )
+}
+
+pub fn state_query_storage_at(keys: Vec<StorageKey>, at_block: Option<Hash>) -> Value {
+    json_req(
+        "state_queryStorageAt",
+        vec![to_value(keys).unwrap(), to_value(at_block).unwrap()],
+        1,
+    )
+}
+
 pub fn state_get_keys(key: StorageKey, at_block: Option<Hash>) -> Value {
     json_req(
         "state_getKeys",
@@ -118,6 +212,99 @@ pub fn state_get_keys(key: StorageKey, at_block: Option<Hash>) -> Value {
     )
 }
 
+pub fn state_get_keys_paged(
+    prefix: StorageKey,
+    count: u32,
+    start_key: Option<StorageKey>,
+    at_block: Option<Hash>,
+) -> Value {
+    json_req(
+        "state_getKeysPaged",
+        vec![
+            to_value(prefix).unwrap(),
+            to_value(count).unwrap(),
+            to_value(start_key).unwrap(),
+            to_value(at_block).unwrap(),
+        ],
+        1,
+    )
+}
+
+pub fn state_get_child_read_proof(
+    child_storage_key: PrefixedStorageKey,
+    keys: Vec<StorageKey>,
+    at_block: Option<Hash>,
+) -> Value {
+    json_req(
+        "state_getChildReadProof",
+        vec![
+            to_value(child_storage_key).unwrap(),
+            to_value(keys).unwrap(),
+            to_value(at_block).unwrap(),
+        ],
+        1,
+    )
+}
+
+pub fn childstate_get_storage(
+    child_storage_key: PrefixedStorageKey,
+    key: StorageKey,
+    at_block: Option<Hash>,
+) -> Value {
+    childstate_request("childstate_getStorage", child_storage_key, key, at_block)
+}
+
+pub fn childstate_get_keys(
+    child_storage_key: PrefixedStorageKey,
+    prefix: StorageKey,
+    at_block: Option<Hash>,
+) -> Value {
+    childstate_request("childstate_getKeys", child_storage_key, prefix, at_block)
+}
+
+pub fn childstate_get_storage_hash(
+    child_storage_key: PrefixedStorageKey,
+    key: StorageKey,
+    at_block: Option<Hash>,
+) -> Value {
+    childstate_request(
+        "childstate_getStorageHash",
+        child_storage_key,
+        key,
+        at_block,
+    )
+}
+
+pub fn childstate_get_storage_size(
+    child_storage_key: PrefixedStorageKey,
+    key: StorageKey,
+    at_block: Option<Hash>,
+) -> Value {
+    childstate_request(
+        "childstate_getStorageSize",
+        child_storage_key,
+        key,
+        at_block,
+    )
+}
+
+fn childstate_request(
+    method: &str,
+    child_storage_key: PrefixedStorageKey,
+    key: StorageKey,
+    at_block: Option<Hash>,
+) -> Value {
+    json_req(
+        method,
+        vec![
+            to_value(child_storage_key).unwrap(),
+            to_value(key).unwrap(),
+            to_value(at_block).unwrap(),
+        ],
+        1,
+    )
+}
+
 pub fn author_submit_extrinsic(xthex_prefixed: &str) -> Value {
     author_submit_extrinsic_with_id(xthex_prefixed, REQUEST_TRANSFER)
 }
@@ -134,7 +321,81 @@ pub fn author_submit_and_watch_extrinsic_with_id(xthex_prefixed: &str, id: u32)
     json_req("author_submitAndWatchExtrinsic", vec![xthex_prefixed], id)
 }
 
-fn json_req<S: Serialize>(method: &str, params: S, id: u32) -> Value {
+pub fn author_pending_extrinsics() -> Value {
+    json_req("author_pendingExtrinsics", Value::Null, 1)
+}
+
+pub fn author_remove_extrinsic(extrinsics: Vec<ExtrinsicOrHash>) -> Value {
+    json_req("author_removeExtrinsic", vec![extrinsics], 1)
+}
+
+pub fn author_rotate_keys() -> Value {
+    json_req("author_rotateKeys", Value::Null, 1)
+}
+
+pub fn author_insert_key(key_type: &str, suri: &str, public: Bytes) -> Value {
+    json_req(
+        "author_insertKey",
+        vec![
+            to_value(key_type).unwrap(),
+            to_value(suri).unwrap(),
+            to_value(public).unwrap(),
+        ],
+        1,
+    )
+}
+
+pub fn author_has_session_keys(session_keys: Bytes) -> Value {
+    json_req("author_hasSessionKeys", vec![session_keys], 1)
+}
+
+pub fn author_has_key(public: Bytes, key_type: &str) -> Value {
+    json_req(
+        "author_hasKey",
+        vec![to_value(public).unwrap(), to_value(key_type).unwrap()],
+        1,
+    )
+}
+
+pub fn offchain_local_storage_get(kind: StorageKind, key: Bytes) -> Value {
+    json_req(
+        "offchain_localStorageGet",
+        vec![to_value(kind).unwrap(), to_value(key).unwrap()],
+        1,
+    )
+}
+
+pub fn offchain_local_storage_set(kind: StorageKind, key: Bytes, value: Bytes) -> Value {
+    json_req(
+        "offchain_localStorageSet",
+        vec![
+            to_value(kind).unwrap(),
+            to_value(key).unwrap(),
+            to_value(value).unwrap(),
+        ],
+        1,
+    )
+}
+
+/// Combines `requests` into one batch request. Each request gets its position in the batch as id.
+pub fn batch(requests: Vec<Value>) -> Value {
+    Value::Array(
+        requests
+            .into_iter()
+            .enumerate()
+            .map(|(id, mut req)| {
+                req["id"] = Value::String(id.to_string());
+                req
+            })
+            .collect(),
+    )
+}
+
+pub fn unsubscribe_with_id(method: &str, subscription_id: Value, id: u32) -> Value {
+    json_req(method, vec![subscription_id], id)
+}
+
+pub(crate) fn json_req<S: Serialize>(method: &str, params: S, id: u32) -> Value {
     json!({
         "method": method,
         "params": params,
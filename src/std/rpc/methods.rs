@@ -0,0 +1,260 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! Typed definitions of the JSON-RPC methods, used with `Api::request`.
+//!
+//! ```no_run
+//! use substrate_api_client::rpc::methods::ChainGetBlockHash;
+//! use substrate_api_client::rpc::WsRpcClient;
+//! use substrate_api_client::Api;
+//! use sp_core::sr25519;
+//!
+//! let api = Api::<sr25519::Pair, _>::new(WsRpcClient::new("ws://127.0.0.1:9944")).unwrap();
+//! let hash = api.request::<ChainGetBlockHash>((Some(1),)).unwrap();
+//! ```
+//!
+//! A new RPC is added by declaring its method name, parameters and response with `rpc_method!`.
+//! Parameters are serialized as the JSON-RPC `params`: a tuple becomes the positional parameter
+//! list, `()` means no parameters. Results that may be `null` are declared as `Option`.
+
+use std::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use sp_core::storage::{PrefixedStorageKey, StorageChangeSet, StorageData, StorageKey};
+use sp_core::Bytes;
+use sp_rpc::number::NumberOrHex;
+use sp_runtime::generic::SignedBlock;
+use sp_version::RuntimeVersion;
+use transaction_payment::{FeeDetails, RuntimeDispatchInfo};
+
+use crate::std::rpc::author::ExtrinsicOrHash;
+use crate::std::rpc::offchain::StorageKind;
+use crate::std::rpc::system::{ChainProperties, ChainType, Health, PeerInfo, SyncState};
+use crate::std::rpc::{json_req, ReadProof, RpcMethodList};
+use crate::{Balance, Hash};
+
+/// A JSON-RPC method together with the types of its parameters and its result.
+pub trait RpcMethod {
+    const NAME: &'static str;
+    type Params: Serialize;
+    type Response: DeserializeOwned;
+
+    fn request(params: Self::Params) -> Value {
+        json_req::json_req(Self::NAME, params, 1)
+    }
+}
+
+macro_rules! rpc_method {
+    ($(#[$attr:meta])* $name:ident = $method:literal, $params:ty => $response:ty) => {
+        $(#[$attr])*
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        pub struct $name;
+
+        impl RpcMethod for $name {
+            const NAME: &'static str = $method;
+            type Params = $params;
+            type Response = $response;
+        }
+    };
+}
+
+rpc_method!(
+    /// Hash of the block with the given number, or of the best block.
+    ChainGetBlockHash = "chain_getBlockHash", (Option<u32>,) => Option<Hash>
+);
+rpc_method!(ChainGetFinalizedHead = "chain_getFinalizedHead", () => Option<Hash>);
+rpc_method!(
+    /// SCALE encoded `RuntimeMetadataPrefixed`.
+    StateGetMetadata = "state_getMetadata", () => Option<Bytes>
+);
+rpc_method!(StateGetRuntimeVersion = "state_getRuntimeVersion", () => Option<RuntimeVersion>);
+rpc_method!(
+    /// Calls a runtime API function with SCALE encoded arguments. Returns the encoded result.
+    StateCall = "state_call", (String, Bytes, Option<Hash>) => Bytes
+);
+rpc_method!(
+    StateGetStorage = "state_getStorage", (StorageKey, Option<Hash>) => Option<StorageData>
+);
+rpc_method!(
+    /// Changes of the given keys from the first to the second block, the best block by default.
+    StateQueryStorage = "state_queryStorage",
+    (Vec<StorageKey>, Hash, Option<Hash>) => Vec<StorageChangeSet<Hash>>
+);
+rpc_method!(
+    /// Values of the given keys at a block.
+    StateQueryStorageAt = "state_queryStorageAt",
+    (Vec<StorageKey>, Option<Hash>) => Vec<StorageChangeSet<Hash>>
+);
+rpc_method!(
+    /// Keys that start with the given prefix.
+    StateGetKeys = "state_getKeys", (StorageKey, Option<Hash>) => Option<Vec<StorageKey>>
+);
+rpc_method!(
+    /// At most `count` keys with the given prefix, following the start key if given.
+    StateGetKeysPaged = "state_getKeysPaged",
+    (StorageKey, u32, Option<StorageKey>, Option<Hash>) => Vec<StorageKey>
+);
+rpc_method!(
+    StateGetReadProof = "state_getReadProof",
+    (Vec<StorageKey>, Option<Hash>) => Option<ReadProof<Hash>>
+);
+rpc_method!(
+    /// Read proof of the given keys of a child trie.
+    StateGetChildReadProof = "state_getChildReadProof",
+    (PrefixedStorageKey, Vec<StorageKey>, Option<Hash>) => Option<ReadProof<Hash>>
+);
+rpc_method!(
+    /// Value of the given key of a child trie.
+    ChildStateGetStorage = "childstate_getStorage",
+    (PrefixedStorageKey, StorageKey, Option<Hash>) => Option<StorageData>
+);
+rpc_method!(
+    /// Keys of a child trie that start with the given prefix.
+    ChildStateGetKeys = "childstate_getKeys",
+    (PrefixedStorageKey, StorageKey, Option<Hash>) => Vec<StorageKey>
+);
+rpc_method!(
+    /// Hash of the value of the given key of a child trie.
+    ChildStateGetStorageHash = "childstate_getStorageHash",
+    (PrefixedStorageKey, StorageKey, Option<Hash>) => Option<Hash>
+);
+rpc_method!(
+    /// Size in bytes of the value of the given key of a child trie.
+    ChildStateGetStorageSize = "childstate_getStorageSize",
+    (PrefixedStorageKey, StorageKey, Option<Hash>) => Option<u64>
+);
+rpc_method!(
+    /// Fee details of the hex encoded extrinsic.
+    PaymentQueryFeeDetails = "payment_queryFeeDetails",
+    (String, Option<Hash>) => Option<FeeDetails<NumberOrHex>>
+);
+rpc_method!(
+    /// Weight, dispatch class and partial fee of the hex encoded extrinsic.
+    PaymentQueryInfo = "payment_queryInfo",
+    (String, Option<Hash>) => RuntimeDispatchInfo<Balance>
+);
+rpc_method!(
+    /// Encoded extrinsics in the transaction pool.
+    AuthorPendingExtrinsics = "author_pendingExtrinsics", () => Vec<Bytes>
+);
+rpc_method!(
+    /// Hashes of the extrinsics that were removed from the transaction pool.
+    AuthorRemoveExtrinsic = "author_removeExtrinsic", (Vec<ExtrinsicOrHash>,) => Vec<Hash>
+);
+rpc_method!(
+    /// Generates new session keys in the node's keystore and returns them encoded.
+    AuthorRotateKeys = "author_rotateKeys", () => Bytes
+);
+rpc_method!(
+    /// Inserts the key of the given type, secret uri and public key into the node's keystore.
+    AuthorInsertKey = "author_insertKey", (String, String, Bytes) => ()
+);
+rpc_method!(
+    /// Whether the node's keystore holds the private keys of all encoded session keys.
+    AuthorHasSessionKeys = "author_hasSessionKeys", (Bytes,) => bool
+);
+rpc_method!(
+    /// Whether the node's keystore holds the private key of the public key and key type.
+    AuthorHasKey = "author_hasKey", (Bytes, String) => bool
+);
+rpc_method!(
+    /// Value of the given key in the node's offchain storage.
+    OffchainLocalStorageGet = "offchain_localStorageGet", (StorageKind, Bytes) => Option<Bytes>
+);
+rpc_method!(
+    /// Sets the given key in the node's offchain storage to the value.
+    OffchainLocalStorageSet = "offchain_localStorageSet", (StorageKind, Bytes, Bytes) => ()
+);
+rpc_method!(RpcMethods = "rpc_methods", () => RpcMethodList);
+rpc_method!(SystemName = "system_name", () => String);
+rpc_method!(SystemVersion = "system_version", () => String);
+rpc_method!(SystemChain = "system_chain", () => String);
+rpc_method!(SystemChainType = "system_chainType", () => ChainType);
+rpc_method!(SystemProperties = "system_properties", () => ChainProperties);
+rpc_method!(SystemHealth = "system_health", () => Health);
+rpc_method!(SystemPeers = "system_peers", () => Vec<PeerInfo>);
+rpc_method!(SystemSyncState = "system_syncState", () => SyncState);
+rpc_method!(SystemLocalPeerId = "system_localPeerId", () => String);
+rpc_method!(
+    /// SCALE encoded `ApplyExtrinsicResult` of applying the extrinsic on top of a block.
+    SystemDryRun = "system_dryRun", (Bytes, Option<Hash>) => Bytes
+);
+
+/// Header of the given block, or of the best block. `H` is the header type of the runtime.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChainGetHeader<H>(PhantomData<H>);
+
+impl<H: DeserializeOwned> RpcMethod for ChainGetHeader<H> {
+    const NAME: &'static str = "chain_getHeader";
+    type Params = (Option<Hash>,);
+    type Response = Option<H>;
+}
+
+/// The given block with its justifications, or the best block. `B` is the block type of the
+/// runtime.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChainGetBlock<B>(PhantomData<B>);
+
+impl<B: DeserializeOwned> RpcMethod for ChainGetBlock<B> {
+    const NAME: &'static str = "chain_getBlock";
+    type Params = (Option<Hash>,);
+    type Response = Option<SignedBlock<B>>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_match_the_json_req_builders() {
+        let key = StorageKey(vec![1, 2]);
+        assert_eq!(
+            StateGetStorage::request((key.clone(), None)),
+            json_req::state_get_storage(key, None)
+        );
+        assert_eq!(
+            ChainGetBlockHash::request((Some(0),)),
+            json_req::chain_get_genesis_hash()
+        );
+        assert_eq!(
+            ChainGetFinalizedHead::request(()),
+            json_req::chain_get_finalized_head()
+        );
+        let child_key = PrefixedStorageKey::new(b":child_storage:default:trie".to_vec());
+        assert_eq!(
+            ChildStateGetStorage::request((child_key.clone(), StorageKey(vec![3]), None)),
+            json_req::childstate_get_storage(child_key, StorageKey(vec![3]), None)
+        );
+    }
+
+    #[test]
+    fn responses_are_decoded() {
+        let hash: <ChainGetBlockHash as RpcMethod>::Response =
+            serde_json::from_str(&format!("\"0x{}\"", "07".repeat(32))).unwrap();
+        assert_eq!(hash, Some(Hash::repeat_byte(7)));
+
+        let storage: <StateGetStorage as RpcMethod>::Response =
+            serde_json::from_str("null").unwrap();
+        assert_eq!(storage, None);
+
+        let metadata: <StateGetMetadata as RpcMethod>::Response =
+            serde_json::from_str("\"0x010203\"").unwrap();
+        assert_eq!(metadata.unwrap().0, vec![1, 2, 3]);
+    }
+}
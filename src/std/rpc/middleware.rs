@@ -0,0 +1,603 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! Wrappers that add retries, logging, metrics and rate limits to any client.
+//!
+//! ```no_run
+//! use substrate_api_client::rpc::{RetryPolicy, RpcClientExt, RpcMetrics, WsRpcClient};
+//!
+//! let metrics = RpcMetrics::new();
+//! metrics.serve("127.0.0.1:9615").unwrap();
+//! let client = WsRpcClient::new("ws://127.0.0.1:9944")
+//!     .with_retry(RetryPolicy::default())
+//!     .with_metrics(metrics)
+//!     .with_logging();
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::{debug, info, warn};
+use serde_json::Value;
+
+#[cfg(feature = "ws-client")]
+use std::sync::mpsc::Sender as ThreadOut;
+
+#[cfg(feature = "ws-client")]
+use crate::std::rpc::ws_client::{Reconnected, Subscriber};
+use crate::std::rpc::{is_idempotent, RateLimit, RateLimitedRpcClient};
+use crate::std::{ApiResult, RpcClient, XtStatus};
+use crate::Hash;
+
+/// Method name extrinsic submissions are reported with.
+const SUBMIT_METHOD: &str = "author_submitAndWatchExtrinsic";
+
+/// Adds the middleware of this module to every client.
+pub trait RpcClientExt: RpcClient + Sized {
+    /// Retries failed requests according to `policy`.
+    fn with_retry(self, policy: RetryPolicy) -> RetryRpcClient<Self> {
+        RetryRpcClient {
+            client: self,
+            policy,
+        }
+    }
+
+    /// Counts requests, errors and latencies per method in `metrics`.
+    fn with_metrics(self, metrics: RpcMetrics) -> MetricsRpcClient<Self> {
+        MetricsRpcClient {
+            client: self,
+            metrics,
+        }
+    }
+
+    /// Logs every request with its duration and outcome.
+    fn with_logging(self) -> LoggingRpcClient<Self> {
+        LoggingRpcClient { client: self }
+    }
+
+    /// Limits the request rate and the in-flight requests, see `RateLimit`.
+    fn with_rate_limit(self, limit: RateLimit) -> RateLimitedRpcClient<Self> {
+        RateLimitedRpcClient::new(self, limit)
+    }
+}
+
+impl<Client: RpcClient> RpcClientExt for Client {}
+
+/// How often and how fast failed requests are retried.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt.
+    pub max_retries: u32,
+    /// Wait before the first retry. Doubled after every retry.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    fn backoff(&self, retry: u32) -> Duration {
+        self.initial_backoff
+            .checked_mul(2u32.saturating_pow(retry))
+            .map_or(self.max_backoff, |b| b.min(self.max_backoff))
+    }
+}
+
+/// Retries requests that failed with a transport error.
+///
+/// Extrinsics are never retried, because the first attempt may have reached the node. For the
+/// same reason, requests that change the node, like `author_rotateKeys`, are only retried if they
+/// could not be sent at all.
+#[derive(Debug, Clone)]
+pub struct RetryRpcClient<Client> {
+    client: Client,
+    policy: RetryPolicy,
+}
+
+impl<Client: RpcClient> RetryRpcClient<Client> {
+    fn retry<T, F>(&self, idempotent: bool, f: F) -> ApiResult<T>
+    where
+        F: Fn(&Client) -> ApiResult<T>,
+    {
+        let mut retry = 0;
+        loop {
+            match f(&self.client) {
+                Err(e)
+                    if e.is_transport_error()
+                        && (idempotent || e.is_unsent())
+                        && retry < self.policy.max_retries =>
+                {
+                    let backoff = self.policy.backoff(retry);
+                    warn!("request failed: {}. retrying in {:?}", e, backoff);
+                    thread::sleep(backoff);
+                    retry += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+}
+
+impl<Client: RpcClient> RpcClient for RetryRpcClient<Client> {
+    fn get_request(&self, jsonreq: Value) -> ApiResult<String> {
+        let idempotent = is_idempotent(&method_name(&jsonreq));
+        self.retry(idempotent, |client| client.get_request(jsonreq.clone()))
+    }
+
+    fn get_batch(&self, jsonreqs: Vec<Value>) -> ApiResult<Vec<Option<String>>> {
+        let idempotent = jsonreqs.iter().all(|r| is_idempotent(&method_name(r)));
+        self.retry(idempotent, |client| client.get_batch(jsonreqs.clone()))
+    }
+
+    fn send_extrinsic(&self, xthex_prefixed: String, exit_on: XtStatus) -> ApiResult<Option<Hash>> {
+        self.watch_extrinsic(xthex_prefixed, exit_on, &mut |_| {})
+    }
+
+    fn watch_extrinsic(
+        &self,
+        xthex_prefixed: String,
+        exit_on: XtStatus,
+        on_status: &mut dyn FnMut(&Value),
+    ) -> ApiResult<Option<Hash>> {
+        self.client
+            .watch_extrinsic(xthex_prefixed, exit_on, on_status)
+    }
+}
+
+/// Request metrics per JSON-RPC method, shared by all clones.
+#[derive(Debug, Clone, Default)]
+pub struct RpcMetrics {
+    methods: Arc<Mutex<BTreeMap<String, MethodMetrics>>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct MethodMetrics {
+    requests: u64,
+    errors: u64,
+    duration_seconds: f64,
+}
+
+impl RpcMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn observe(&self, method: &str, duration: Duration, failed: bool) {
+        let mut methods = self.methods.lock().unwrap();
+        let metrics = methods.entry(method.to_string()).or_default();
+        metrics.requests += 1;
+        if failed {
+            metrics.errors += 1;
+        }
+        metrics.duration_seconds += duration.as_secs_f64();
+    }
+
+    /// Renders the metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let methods = self.methods.lock().unwrap();
+        let mut out = String::new();
+
+        let name = "substrate_api_client_requests_total";
+        write_header(
+            &mut out,
+            name,
+            "counter",
+            "JSON-RPC requests sent, by method.",
+        );
+        for (method, metrics) in methods.iter() {
+            write_sample(&mut out, name, method, metrics.requests);
+        }
+
+        let name = "substrate_api_client_request_errors_total";
+        write_header(
+            &mut out,
+            name,
+            "counter",
+            "JSON-RPC requests that failed, by method.",
+        );
+        for (method, metrics) in methods.iter() {
+            write_sample(&mut out, name, method, metrics.errors);
+        }
+
+        let name = "substrate_api_client_request_duration_seconds";
+        let help = "Time spent waiting for JSON-RPC responses, by method.";
+        write_header(&mut out, name, "summary", help);
+        for (method, metrics) in methods.iter() {
+            let sum = format!("{}_sum", name);
+            write_sample(&mut out, &sum, method, metrics.duration_seconds);
+            let count = format!("{}_count", name);
+            write_sample(&mut out, &count, method, metrics.requests);
+        }
+        out
+    }
+
+    /// Serves `render` over HTTP on `addr` from a background thread, for a local Prometheus to
+    /// scrape. Every path returns the metrics.
+    pub fn serve<A: ToSocketAddrs>(&self, addr: A) -> io::Result<thread::JoinHandle<()>> {
+        let listener = TcpListener::bind(addr)?;
+        let metrics = self.clone();
+        thread::Builder::new()
+            .name("rpc-metrics".to_owned())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    let mut stream = match stream {
+                        Ok(stream) => stream,
+                        Err(e) => {
+                            warn!("metrics connection failed: {}", e);
+                            continue;
+                        }
+                    };
+                    // the request itself does not matter
+                    let mut request = [0u8; 1024];
+                    let _ = stream.read(&mut request);
+                    let body = metrics.render();
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    );
+                    if let Err(e) = stream.write_all(response.as_bytes()) {
+                        debug!("could not send metrics: {}", e);
+                    }
+                }
+            })
+    }
+}
+
+/// Records every request in an `RpcMetrics`.
+///
+/// The requests of a batch are each counted with the duration of the whole batch. Extrinsic
+/// submissions are counted as `author_submitAndWatchExtrinsic`.
+#[derive(Debug, Clone)]
+pub struct MetricsRpcClient<Client> {
+    client: Client,
+    metrics: RpcMetrics,
+}
+
+impl<Client> MetricsRpcClient<Client> {
+    pub fn metrics(&self) -> &RpcMetrics {
+        &self.metrics
+    }
+}
+
+impl<Client: RpcClient> RpcClient for MetricsRpcClient<Client> {
+    fn get_request(&self, jsonreq: Value) -> ApiResult<String> {
+        let method = method_name(&jsonreq);
+        let start = Instant::now();
+        let res = self.client.get_request(jsonreq);
+        self.metrics.observe(&method, start.elapsed(), res.is_err());
+        res
+    }
+
+    fn get_batch(&self, jsonreqs: Vec<Value>) -> ApiResult<Vec<Option<String>>> {
+        let methods: Vec<String> = jsonreqs.iter().map(method_name).collect();
+        let start = Instant::now();
+        let res = self.client.get_batch(jsonreqs);
+        let duration = start.elapsed();
+        for method in methods {
+            self.metrics.observe(&method, duration, res.is_err());
+        }
+        res
+    }
+
+    fn send_extrinsic(&self, xthex_prefixed: String, exit_on: XtStatus) -> ApiResult<Option<Hash>> {
+        self.watch_extrinsic(xthex_prefixed, exit_on, &mut |_| {})
+    }
+
+    fn watch_extrinsic(
+        &self,
+        xthex_prefixed: String,
+        exit_on: XtStatus,
+        on_status: &mut dyn FnMut(&Value),
+    ) -> ApiResult<Option<Hash>> {
+        let start = Instant::now();
+        let res = self
+            .client
+            .watch_extrinsic(xthex_prefixed, exit_on, on_status);
+        self.metrics
+            .observe(SUBMIT_METHOD, start.elapsed(), res.is_err());
+        res
+    }
+}
+
+/// Logs every request with its duration and outcome.
+#[derive(Debug, Clone)]
+pub struct LoggingRpcClient<Client> {
+    client: Client,
+}
+
+impl<Client: RpcClient> RpcClient for LoggingRpcClient<Client> {
+    fn get_request(&self, jsonreq: Value) -> ApiResult<String> {
+        debug!("request: {}", jsonreq);
+        let method = method_name(&jsonreq);
+        let start = Instant::now();
+        let res = self.client.get_request(jsonreq);
+        log_result(&method, start, &res);
+        res
+    }
+
+    fn get_batch(&self, jsonreqs: Vec<Value>) -> ApiResult<Vec<Option<String>>> {
+        debug!("batch request: {:?}", jsonreqs);
+        let method = format!("batch of {}", jsonreqs.len());
+        let start = Instant::now();
+        let res = self.client.get_batch(jsonreqs);
+        log_result(&method, start, &res);
+        res
+    }
+
+    fn send_extrinsic(&self, xthex_prefixed: String, exit_on: XtStatus) -> ApiResult<Option<Hash>> {
+        self.watch_extrinsic(xthex_prefixed, exit_on, &mut |_| {})
+    }
+
+    fn watch_extrinsic(
+        &self,
+        xthex_prefixed: String,
+        exit_on: XtStatus,
+        on_status: &mut dyn FnMut(&Value),
+    ) -> ApiResult<Option<Hash>> {
+        debug!("extrinsic: {}, waiting for {:?}", xthex_prefixed, exit_on);
+        let start = Instant::now();
+        let res = self
+            .client
+            .watch_extrinsic(xthex_prefixed, exit_on, &mut |status| {
+                debug!("extrinsic status: {}", status);
+                on_status(status)
+            });
+        log_result(SUBMIT_METHOD, start, &res);
+        res
+    }
+}
+
+#[cfg(feature = "ws-client")]
+impl<Client: Subscriber> Subscriber for RetryRpcClient<Client> {
+    fn start_subscriber(&self, json_req: String, result_in: ThreadOut<String>) -> ApiResult<()> {
+        self.client.start_subscriber(json_req, result_in)
+    }
+
+    fn start_resumable_subscriber(
+        &self,
+        json_req: String,
+        result_in: ThreadOut<String>,
+        reconnected_in: ThreadOut<Reconnected>,
+    ) -> ApiResult<()> {
+        self.client
+            .start_resumable_subscriber(json_req, result_in, reconnected_in)
+    }
+}
+
+/// Subscriptions are counted as requests of their subscribe method, without duration.
+#[cfg(feature = "ws-client")]
+impl<Client: Subscriber> Subscriber for MetricsRpcClient<Client> {
+    fn start_subscriber(&self, json_req: String, result_in: ThreadOut<String>) -> ApiResult<()> {
+        let res = self.client.start_subscriber(json_req.clone(), result_in);
+        self.observe_subscription(&json_req, res.is_err());
+        res
+    }
+
+    fn start_resumable_subscriber(
+        &self,
+        json_req: String,
+        result_in: ThreadOut<String>,
+        reconnected_in: ThreadOut<Reconnected>,
+    ) -> ApiResult<()> {
+        let res =
+            self.client
+                .start_resumable_subscriber(json_req.clone(), result_in, reconnected_in);
+        self.observe_subscription(&json_req, res.is_err());
+        res
+    }
+}
+
+#[cfg(feature = "ws-client")]
+impl<Client> MetricsRpcClient<Client> {
+    fn observe_subscription(&self, json_req: &str, failed: bool) {
+        let method = serde_json::from_str(json_req)
+            .map(|req: Value| method_name(&req))
+            .unwrap_or_else(|_| "unknown".to_string());
+        self.metrics.observe(&method, Duration::default(), failed);
+    }
+}
+
+#[cfg(feature = "ws-client")]
+impl<Client: Subscriber> Subscriber for LoggingRpcClient<Client> {
+    fn start_subscriber(&self, json_req: String, result_in: ThreadOut<String>) -> ApiResult<()> {
+        info!("subscribing: {}", json_req);
+        self.client.start_subscriber(json_req, result_in)
+    }
+
+    fn start_resumable_subscriber(
+        &self,
+        json_req: String,
+        result_in: ThreadOut<String>,
+        reconnected_in: ThreadOut<Reconnected>,
+    ) -> ApiResult<()> {
+        info!("subscribing: {}", json_req);
+        self.client
+            .start_resumable_subscriber(json_req, result_in, reconnected_in)
+    }
+}
+
+fn method_name(jsonreq: &Value) -> String {
+    jsonreq["method"].as_str().unwrap_or("unknown").to_string()
+}
+
+fn log_result<T>(method: &str, start: Instant, res: &ApiResult<T>) {
+    match res {
+        Ok(_) => info!("{} succeeded after {:?}", method, start.elapsed()),
+        Err(e) => warn!("{} failed after {:?}: {}", method, start.elapsed(), e),
+    }
+}
+
+fn write_header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn write_sample<V: std::fmt::Display>(out: &mut String, name: &str, method: &str, value: V) {
+    let _ = writeln!(
+        out,
+        "{}{{method=\"{}\"}} {}",
+        name,
+        escape_label(method),
+        value
+    );
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::std::rpc::json_req;
+    use crate::std::ApiClientError;
+    use std::cell::Cell;
+
+    struct TestClient {
+        failures: Cell<u32>,
+        requests: Cell<u32>,
+    }
+
+    impl TestClient {
+        fn failing(failures: u32) -> Self {
+            Self {
+                failures: Cell::new(failures),
+                requests: Cell::new(0),
+            }
+        }
+    }
+
+    impl RpcClient for TestClient {
+        fn get_request(&self, jsonreq: Value) -> ApiResult<String> {
+            self.requests.set(self.requests.get() + 1);
+            if jsonreq["method"] == "system_health" {
+                return Err(ApiClientError::Rpc {
+                    code: -32601,
+                    message: "Method not found".to_string(),
+                    data: None,
+                });
+            }
+            if self.failures.get() > 0 {
+                self.failures.set(self.failures.get() - 1);
+                return Err(ApiClientError::Timeout);
+            }
+            Ok("\"0x01\"".to_string())
+        }
+
+        fn send_extrinsic(&self, _: String, _: XtStatus) -> ApiResult<Option<Hash>> {
+            Err(ApiClientError::Timeout)
+        }
+    }
+
+    fn policy(max_retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_retries,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(1),
+        }
+    }
+
+    #[test]
+    fn transport_errors_are_retried() {
+        let client = TestClient::failing(2).with_retry(policy(2));
+        assert_eq!(
+            client
+                .get_request(json_req::chain_get_finalized_head())
+                .unwrap(),
+            "\"0x01\""
+        );
+        assert_eq!(client.client.requests.get(), 3);
+
+        let client = TestClient::failing(2).with_retry(policy(1));
+        assert!(matches!(
+            client.get_request(json_req::chain_get_finalized_head()),
+            Err(ApiClientError::Timeout)
+        ));
+    }
+
+    #[test]
+    fn node_errors_and_extrinsics_are_not_retried() {
+        let client = TestClient::failing(0).with_retry(policy(3));
+        assert!(client.get_request(json_req::system_health()).is_err());
+        assert!(client
+            .send_extrinsic("0x00".to_string(), XtStatus::Ready)
+            .is_err());
+        assert_eq!(client.client.requests.get(), 1);
+    }
+
+    #[test]
+    fn timed_out_writes_are_not_retried() {
+        let client = TestClient::failing(1).with_retry(policy(3));
+        assert!(matches!(
+            client.get_request(json_req::author_rotate_keys()),
+            Err(ApiClientError::Timeout)
+        ));
+        assert_eq!(client.client.requests.get(), 1);
+
+        let client = TestClient::failing(1).with_retry(policy(3));
+        assert!(client
+            .get_batch(vec![
+                json_req::chain_get_finalized_head(),
+                json_req::author_rotate_keys()
+            ])
+            .is_err());
+        assert_eq!(client.client.requests.get(), 1);
+    }
+
+    #[test]
+    fn metrics_are_counted_per_method() {
+        let client = TestClient::failing(0).with_metrics(RpcMetrics::new());
+        client
+            .get_request(json_req::chain_get_finalized_head())
+            .unwrap();
+        client
+            .get_batch(vec![
+                json_req::chain_get_finalized_head(),
+                json_req::system_health(),
+            ])
+            .unwrap_err();
+
+        let rendered = client.metrics().render();
+        assert!(rendered.contains("# TYPE substrate_api_client_requests_total counter\n"));
+        assert!(rendered.contains(
+            "substrate_api_client_requests_total{method=\"chain_getFinalizedHead\"} 2\n"
+        ));
+        assert!(rendered
+            .contains("substrate_api_client_request_errors_total{method=\"system_health\"} 1\n"));
+        assert!(rendered.contains("# TYPE substrate_api_client_request_duration_seconds summary\n"));
+        assert!(rendered.contains(
+            "substrate_api_client_request_duration_seconds_count{method=\"system_health\"} 1\n"
+        ));
+    }
+}
@@ -0,0 +1,690 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! An in-process node for tests that do not want to depend on a running node.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::mpsc::RecvError;
+use std::sync::{Arc, Mutex};
+
+use codec::Encode;
+use log::debug;
+use serde_json::{json, Value};
+use sp_core::storage::StorageKey;
+use sp_core::Bytes;
+use sp_version::RuntimeVersion;
+
+#[cfg(feature = "ws-client")]
+use std::sync::mpsc::Sender as ThreadOut;
+
+#[cfg(feature = "ws-client")]
+use crate::std::rpc::ws_client::Subscriber;
+use crate::std::{ApiClientError, ApiResult, RpcClient, XtStatus};
+use crate::{utils, Hash};
+
+/// Answers requests from programmable state instead of a real node.
+///
+/// Storage is keyed by the `StorageKey`s `Metadata` computes, so a test usually creates an `Api`
+/// on a clone of the node and then fills the storage with keys from `api.metadata`. All clones
+/// share the same state.
+///
+/// Submitted extrinsics run through the next script pushed with `push_extrinsic_script`, or
+/// through `Ready`, `Broadcast`, `InBlock`, `Finalized` if there is none.
+///
+/// Runtime API calls with `state_call` are answered with the results set with
+/// `set_runtime_call` and recorded with their arguments.
+#[derive(Debug, Clone)]
+pub struct MockNode {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    genesis_hash: Hash,
+    block_hash: Hash,
+    metadata: Vec<u8>,
+    runtime_version: RuntimeVersion,
+    storage: HashMap<StorageKey, Vec<u8>>,
+    responses: HashMap<String, Value>,
+    runtime_results: HashMap<String, Vec<u8>>,
+    runtime_calls: Vec<RuntimeCall>,
+    extrinsic_scripts: VecDeque<Vec<XtStatus>>,
+    submitted_extrinsics: Vec<String>,
+    #[cfg(feature = "ws-client")]
+    storage_subscribers: Vec<(Vec<StorageKey>, ThreadOut<String>)>,
+    #[cfg(feature = "ws-client")]
+    head_subscribers: Vec<ThreadOut<String>>,
+}
+
+/// A `state_call` the node received.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeCall {
+    /// Runtime API function, e.g. `Core_version`.
+    pub method: String,
+    /// SCALE encoded arguments.
+    pub args: Vec<u8>,
+    pub at: Option<Hash>,
+}
+
+impl MockNode {
+    /// `metadata` is the SCALE encoded `RuntimeMetadataPrefixed` served by `state_getMetadata`.
+    pub fn new(metadata: Vec<u8>) -> Self {
+        let state = State {
+            metadata,
+            block_hash: Hash::repeat_byte(1),
+            ..Default::default()
+        };
+        Self {
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    pub fn set_genesis_hash(self, genesis_hash: Hash) -> Self {
+        self.state().genesis_hash = genesis_hash;
+        self
+    }
+
+    pub fn set_runtime_version(self, runtime_version: RuntimeVersion) -> Self {
+        self.state().runtime_version = runtime_version;
+        self
+    }
+
+    /// Sets the block extrinsics are included in and finalized with. It is also the finalized
+    /// head of the node.
+    pub fn set_block_hash(self, block_hash: Hash) -> Self {
+        self.state().block_hash = block_hash;
+        self
+    }
+
+    pub fn insert_storage<V: Encode>(&self, key: StorageKey, value: &V) {
+        self.insert_opaque_storage(key, value.encode())
+    }
+
+    pub fn insert_opaque_storage(&self, key: StorageKey, value: Vec<u8>) {
+        self.state().storage.insert(key, value);
+    }
+
+    pub fn remove_storage(&self, key: &StorageKey) {
+        self.state().storage.remove(key);
+    }
+
+    /// Answers all requests of `method` with `result`, overriding the built-in answer.
+    pub fn set_response(&self, method: &str, result: Value) {
+        self.state().responses.insert(method.to_string(), result);
+    }
+
+    /// Answers calls of the runtime API function `method` with the SCALE encoded `result`.
+    pub fn set_runtime_call<R: Encode>(&self, method: &str, result: &R) {
+        self.set_opaque_runtime_call(method, result.encode())
+    }
+
+    pub fn set_opaque_runtime_call(&self, method: &str, result: Vec<u8>) {
+        self.state()
+            .runtime_results
+            .insert(method.to_string(), result);
+    }
+
+    /// Runtime API calls in the order they were made.
+    pub fn runtime_calls(&self) -> Vec<RuntimeCall> {
+        self.state().runtime_calls.clone()
+    }
+
+    /// The next submitted extrinsic goes through `statuses`. `XtStatus::Error` makes the node
+    /// reject it, scripts that end before the awaited status drop the watch.
+    pub fn push_extrinsic_script(&self, statuses: Vec<XtStatus>) {
+        self.state().extrinsic_scripts.push_back(statuses);
+    }
+
+    /// Hex encoded extrinsics in the order they were submitted.
+    pub fn submitted_extrinsics(&self) -> Vec<String> {
+        self.state().submitted_extrinsics.clone()
+    }
+
+    /// Stores the SCALE encoded event records as `System.Events` and sends them to all event
+    /// subscribers.
+    pub fn emit_events(&self, events: Vec<u8>) {
+        let key = utils::storage_key("System", "Events");
+        let mut state = self.state();
+        #[cfg(feature = "ws-client")]
+        {
+            let change = format!("0x{}", hex::encode(&events));
+            state.storage_subscribers.retain(|(keys, subscriber)| {
+                !keys.contains(&key) || subscriber.send(change.clone()).is_ok()
+            });
+        }
+        state.storage.insert(key, events);
+    }
+
+    /// Sends `header` to all subscribers of finalized heads.
+    #[cfg(feature = "ws-client")]
+    pub fn emit_finalized_head(&self, header: Value) {
+        let header = header.to_string();
+        self.state()
+            .head_subscribers
+            .retain(|subscriber| subscriber.send(header.clone()).is_ok());
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<State> {
+        self.state.lock().unwrap()
+    }
+
+    fn answer(&self, method: &str, params: &Value) -> ApiResult<Value> {
+        let mut state = self.state();
+        if let Some(result) = state.responses.get(method) {
+            return Ok(result.clone());
+        }
+
+        let result = match method {
+            "chain_getBlockHash" => match params[0].as_u64() {
+                Some(0) => json!(state.genesis_hash),
+                Some(_) => Value::Null,
+                None => json!(state.block_hash),
+            },
+            "chain_getFinalizedHead" => json!(state.block_hash),
+            "state_getMetadata" => json!(hex_encode(&state.metadata)),
+            "state_getRuntimeVersion" => serde_json::to_value(&state.runtime_version)?,
+            "state_getStorage" => {
+                let key: StorageKey = serde_json::from_value(params[0].clone())?;
+                json!(state.storage.get(&key).map(|v| hex_encode(v.as_slice())))
+            }
+            "state_getKeys" => {
+                let prefix: StorageKey = serde_json::from_value(params[0].clone())?;
+                json!(state.keys_with_prefix(&prefix))
+            }
+            "state_getKeysPaged" => {
+                let prefix: StorageKey = serde_json::from_value(params[0].clone())?;
+                let count = params[1].as_u64().unwrap_or_default() as usize;
+                let start_key: Option<StorageKey> = serde_json::from_value(params[2].clone())?;
+                let keys: Vec<&StorageKey> = state
+                    .keys_with_prefix(&prefix)
+                    .into_iter()
+                    .filter(|k| start_key.as_ref().map_or(true, |start| k.0 > start.0))
+                    .take(count)
+                    .collect();
+                json!(keys)
+            }
+            "state_queryStorageAt" => {
+                let keys: Vec<StorageKey> = serde_json::from_value(params[0].clone())?;
+                let changes: Vec<Value> = keys
+                    .iter()
+                    .map(|k| json!([k, state.storage.get(k).map(|v| hex_encode(v.as_slice()))]))
+                    .collect();
+                json!([{ "block": state.block_hash, "changes": changes }])
+            }
+            "state_call" => {
+                let call = RuntimeCall {
+                    method: serde_json::from_value(params[0].clone())?,
+                    args: serde_json::from_value::<Bytes>(params[1].clone())?.0,
+                    at: serde_json::from_value(params[2].clone())?,
+                };
+                let result = state.runtime_results.get(&call.method).cloned();
+                let method = call.method.clone();
+                state.runtime_calls.push(call);
+                match result {
+                    Some(result) => json!(hex_encode(&result)),
+                    // a node fails to execute runtime API functions it does not export
+                    None => {
+                        return Err(ApiClientError::Rpc {
+                            code: -32603,
+                            message: "Unknown error occurred".to_string(),
+                            data: Some(format!("Exported method {} is not found", method)),
+                        })
+                    }
+                }
+            }
+            "system_health" => json!({"peers": 0, "isSyncing": false, "shouldHavePeers": false}),
+            _ => {
+                return Err(ApiClientError::Rpc {
+                    code: -32601,
+                    message: "Method not found".to_string(),
+                    data: None,
+                })
+            }
+        };
+        Ok(result)
+    }
+}
+
+impl State {
+    /// Keys in the order the node lists them.
+    fn keys_with_prefix(&self, prefix: &StorageKey) -> Vec<&StorageKey> {
+        let mut keys: Vec<&StorageKey> = self
+            .storage
+            .keys()
+            .filter(|k| k.0.starts_with(&prefix.0))
+            .collect();
+        keys.sort_by(|a, b| a.0.cmp(&b.0));
+        keys
+    }
+}
+
+impl RpcClient for MockNode {
+    fn get_request(&self, jsonreq: Value) -> ApiResult<String> {
+        let method = jsonreq["method"].as_str().unwrap_or_default();
+        debug!("mock node got request {}", jsonreq);
+        self.answer(method, &jsonreq["params"])
+            .map(|result| result.to_string())
+    }
+
+    fn send_extrinsic(&self, xthex_prefixed: String, exit_on: XtStatus) -> ApiResult<Option<Hash>> {
+        self.watch_extrinsic(xthex_prefixed, exit_on, &mut |_| {})
+    }
+
+    fn watch_extrinsic(
+        &self,
+        xthex_prefixed: String,
+        exit_on: XtStatus,
+        on_status: &mut dyn FnMut(&Value),
+    ) -> ApiResult<Option<Hash>> {
+        let (script, block_hash) = {
+            let mut state = self.state();
+            state.submitted_extrinsics.push(xthex_prefixed);
+            let script = state.extrinsic_scripts.pop_front().unwrap_or_else(|| {
+                vec![
+                    XtStatus::Ready,
+                    XtStatus::Broadcast,
+                    XtStatus::InBlock,
+                    XtStatus::Finalized,
+                ]
+            });
+            (script, state.block_hash)
+        };
+
+        for status in script {
+            if let Some(update) = status_update(&status, block_hash) {
+                on_status(&update);
+            }
+            // the same statuses end a watch as in the WebSocket client
+            match (&exit_on, status) {
+                (_, XtStatus::Error) => {
+                    return Err(ApiClientError::Rpc {
+                        code: 1010,
+                        message: "Invalid Transaction".to_string(),
+                        data: None,
+                    })
+                }
+                (_, XtStatus::Future) => return Ok(None),
+                (_, XtStatus::Finalized) => return Ok(Some(block_hash)),
+                (XtStatus::InBlock, XtStatus::InBlock) => return Ok(Some(block_hash)),
+                (XtStatus::Broadcast, XtStatus::Broadcast) | (XtStatus::Ready, XtStatus::Ready) => {
+                    return Ok(None)
+                }
+                _ => {}
+            }
+        }
+        Err(ApiClientError::Disconnected(RecvError))
+    }
+}
+
+/// The `author_extrinsicUpdate` a node sends for `status`. Rejected extrinsics get none.
+fn status_update(status: &XtStatus, block_hash: Hash) -> Option<Value> {
+    match status {
+        XtStatus::Ready => Some(json!("ready")),
+        XtStatus::Future => Some(json!("future")),
+        XtStatus::Broadcast => Some(json!({ "broadcast": [] })),
+        XtStatus::InBlock => Some(json!({ "inBlock": block_hash })),
+        XtStatus::Finalized => Some(json!({ "finalized": block_hash })),
+        _ => None,
+    }
+}
+
+#[cfg(feature = "ws-client")]
+impl Subscriber for MockNode {
+    fn start_subscriber(&self, json_req: String, result_in: ThreadOut<String>) -> ApiResult<()> {
+        let jsonreq: Value = serde_json::from_str(&json_req)?;
+        let mut state = self.state();
+        match jsonreq["method"].as_str() {
+            Some("state_subscribeStorage") => {
+                let keys = serde_json::from_value(jsonreq["params"][0].clone())?;
+                state.storage_subscribers.push((keys, result_in));
+            }
+            Some("chain_subscribeFinalizedHeads") => state.head_subscribers.push(result_in),
+            _ => {
+                return Err(ApiClientError::RpcClient(format!(
+                    "mock node does not support subscription {}",
+                    json_req
+                )))
+            }
+        }
+        Ok(())
+    }
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    format!("0x{}", hex::encode(bytes))
+}
+
+/// SCALE encoded metadata of a runtime with the `System` and `Balances` items the client uses
+/// itself and the `Assets` double map, for tests that create an `Api` on a `MockNode`.
+#[cfg(test)]
+pub(crate) fn test_metadata() -> Vec<u8> {
+    use metadata::{
+        DecodeDifferent, DecodeDifferentStr, ErrorMetadata, EventMetadata, ExtrinsicMetadata,
+        FunctionMetadata, ModuleConstantMetadata, ModuleMetadata, RuntimeMetadata,
+        RuntimeMetadataPrefixed, RuntimeMetadataV13, StorageEntryMetadata, StorageEntryModifier,
+        StorageEntryType, StorageHasher, StorageMetadata, META_RESERVED,
+    };
+
+    fn text(s: &str) -> DecodeDifferentStr {
+        DecodeDifferent::Decoded(s.to_string())
+    }
+
+    fn entry(name: &str, ty: StorageEntryType) -> StorageEntryMetadata {
+        StorageEntryMetadata {
+            name: text(name),
+            modifier: StorageEntryModifier::Optional,
+            ty,
+            default: DecodeDifferent::Decoded(vec![]),
+            documentation: DecodeDifferent::Decoded(vec![]),
+        }
+    }
+
+    fn event(name: &str, arguments: &[&str]) -> EventMetadata {
+        EventMetadata {
+            name: text(name),
+            arguments: DecodeDifferent::Decoded(arguments.iter().map(|a| a.to_string()).collect()),
+            documentation: DecodeDifferent::Decoded(vec![]),
+        }
+    }
+
+    fn error(name: &str) -> ErrorMetadata {
+        ErrorMetadata {
+            name: text(name),
+            documentation: DecodeDifferent::Decoded(vec![]),
+        }
+    }
+
+    let system = ModuleMetadata {
+        name: text("System"),
+        storage: Some(DecodeDifferent::Decoded(StorageMetadata {
+            prefix: text("System"),
+            entries: DecodeDifferent::Decoded(vec![
+                entry(
+                    "Account",
+                    StorageEntryType::Map {
+                        hasher: StorageHasher::Blake2_128Concat,
+                        key: text("T::AccountId"),
+                        value: text("AccountInfo<T::Index, T::AccountData>"),
+                        unused: false,
+                    },
+                ),
+                entry(
+                    "Events",
+                    StorageEntryType::Plain(text("Vec<EventRecord<T::Event, T::Hash>>")),
+                ),
+            ]),
+        })),
+        calls: Some(DecodeDifferent::Decoded(vec![])),
+        event: Some(DecodeDifferent::Decoded(vec![
+            event("ExtrinsicSuccess", &["DispatchInfo"]),
+            event("ExtrinsicFailed", &["DispatchError", "DispatchInfo"]),
+        ])),
+        constants: DecodeDifferent::Decoded(vec![]),
+        errors: DecodeDifferent::Decoded(vec![]),
+        index: 0,
+    };
+    let balances = ModuleMetadata {
+        name: text("Balances"),
+        storage: None,
+        calls: Some(DecodeDifferent::Decoded(vec![FunctionMetadata {
+            name: text("transfer"),
+            arguments: DecodeDifferent::Decoded(vec![]),
+            documentation: DecodeDifferent::Decoded(vec![]),
+        }])),
+        event: Some(DecodeDifferent::Decoded(vec![
+            event("Endowed", &["AccountId", "Balance"]),
+            event("Transfer", &["AccountId", "AccountId", "Balance"]),
+        ])),
+        constants: DecodeDifferent::Decoded(vec![ModuleConstantMetadata {
+            name: text("ExistentialDeposit"),
+            ty: text("T::Balance"),
+            value: DecodeDifferent::Decoded(500u128.encode()),
+            documentation: DecodeDifferent::Decoded(vec![]),
+        }]),
+        errors: DecodeDifferent::Decoded(vec![
+            error("VestingBalance"),
+            error("LiquidityRestrictions"),
+            error("InsufficientBalance"),
+        ]),
+        index: 1,
+    };
+    let assets = ModuleMetadata {
+        name: text("Assets"),
+        storage: Some(DecodeDifferent::Decoded(StorageMetadata {
+            prefix: text("Assets"),
+            entries: DecodeDifferent::Decoded(vec![entry(
+                "Account",
+                StorageEntryType::DoubleMap {
+                    hasher: StorageHasher::Blake2_128Concat,
+                    key1: text("T::AssetId"),
+                    key2: text("T::AccountId"),
+                    value: text("T::Balance"),
+                    key2_hasher: StorageHasher::Blake2_128Concat,
+                },
+            )]),
+        })),
+        calls: None,
+        event: None,
+        constants: DecodeDifferent::Decoded(vec![]),
+        errors: DecodeDifferent::Decoded(vec![]),
+        index: 2,
+    };
+
+    RuntimeMetadataPrefixed(
+        META_RESERVED,
+        RuntimeMetadata::V13(RuntimeMetadataV13 {
+            modules: DecodeDifferent::Decoded(vec![system, balances, assets]),
+            extrinsic: ExtrinsicMetadata {
+                version: 4,
+                signed_extensions: vec![],
+            },
+        }),
+    )
+    .encode()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::std::rpc::json_req;
+    use crate::std::{AccountId, Api};
+    use crate::{AccountData, AccountInfo, Balance};
+    use codec::Compact;
+    use sp_core::sr25519;
+
+    fn node() -> MockNode {
+        MockNode::new(vec![1, 2, 3]).set_genesis_hash(Hash::repeat_byte(7))
+    }
+
+    #[test]
+    fn answers_from_programmed_state() {
+        let node = node();
+        let key = utils::storage_key("Balances", "TotalIssuance");
+        node.insert_storage(key.clone(), &1000u128);
+
+        assert_eq!(
+            node.get_request(json_req::chain_get_genesis_hash())
+                .unwrap(),
+            format!("\"0x{}\"", "07".repeat(32))
+        );
+        assert_eq!(
+            node.get_request(json_req::state_get_metadata()).unwrap(),
+            "\"0x010203\""
+        );
+        assert_eq!(
+            node.get_request(json_req::state_get_storage(key.clone(), None))
+                .unwrap(),
+            format!("\"{}\"", hex_encode(&1000u128.encode()))
+        );
+
+        node.remove_storage(&key);
+        assert_eq!(
+            node.get_request(json_req::state_get_storage(key, None))
+                .unwrap(),
+            "null"
+        );
+        assert!(matches!(
+            node.get_request(json_req::payment_query_fee_details("0x00", None)),
+            Err(ApiClientError::Rpc { code: -32601, .. })
+        ));
+
+        node.set_response("payment_queryFeeDetails", json!({"tip": "0x0"}));
+        assert_eq!(
+            node.get_request(json_req::payment_query_fee_details("0x00", None))
+                .unwrap(),
+            r#"{"tip":"0x0"}"#
+        );
+    }
+
+    #[test]
+    fn extrinsics_follow_their_script() {
+        let node = node().set_block_hash(Hash::repeat_byte(9));
+
+        assert_eq!(
+            node.send_extrinsic("0x01".to_string(), XtStatus::InBlock)
+                .unwrap(),
+            Some(Hash::repeat_byte(9))
+        );
+
+        node.push_extrinsic_script(vec![XtStatus::Ready, XtStatus::Error]);
+        assert!(matches!(
+            node.send_extrinsic("0x02".to_string(), XtStatus::Finalized),
+            Err(ApiClientError::Rpc { code: 1010, .. })
+        ));
+
+        node.push_extrinsic_script(vec![XtStatus::Ready]);
+        assert!(matches!(
+            node.send_extrinsic("0x03".to_string(), XtStatus::Finalized),
+            Err(ApiClientError::Disconnected(_))
+        ));
+
+        assert_eq!(node.submitted_extrinsics(), vec!["0x01", "0x02", "0x03"]);
+    }
+
+    #[cfg(feature = "ws-client")]
+    #[test]
+    fn events_are_sent_to_subscribers() {
+        let node = node();
+        let key = utils::storage_key("System", "Events");
+        let (events_in, events_out) = std::sync::mpsc::channel();
+        node.start_subscriber(
+            json_req::state_subscribe_storage(vec![key.clone()]).to_string(),
+            events_in,
+        )
+        .unwrap();
+
+        node.emit_events(vec![4, 5]);
+        assert_eq!(events_out.recv().unwrap(), "0x0405");
+        assert_eq!(
+            node.get_request(json_req::state_get_storage(key, None))
+                .unwrap(),
+            "\"0x0405\""
+        );
+    }
+
+    fn api(node: &MockNode) -> Api<sr25519::Pair, MockNode> {
+        Api::new(node.clone()).unwrap()
+    }
+
+    #[test]
+    fn api_reads_account_info_from_the_node() {
+        let node = MockNode::new(test_metadata());
+        let api = api(&node);
+        let alice = AccountId::from([1; 32]);
+        let info = AccountInfo {
+            nonce: 3,
+            providers: 1,
+            data: AccountData {
+                free: 1000,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let key = api
+            .metadata
+            .storage_map_key::<AccountId, AccountInfo>("System", "Account", alice.clone())
+            .unwrap();
+        node.insert_storage(key, &info);
+
+        assert_eq!(api.get_account_info(&alice).unwrap(), Some(info));
+        assert_eq!(
+            api.get_account_info(&AccountId::from([2; 32])).unwrap(),
+            None
+        );
+        assert_eq!(api.get_existential_deposit().unwrap(), 500);
+    }
+
+    #[cfg(feature = "ws-client")]
+    #[test]
+    fn api_waits_for_events_emitted_by_the_node() {
+        let node = MockNode::new(test_metadata());
+        let api = api(&node);
+        let (events_in, events_out) = std::sync::mpsc::channel();
+        api.subscribe_events(events_in).unwrap();
+
+        let from = AccountId::from([1; 32]);
+        let to = AccountId::from([2; 32]);
+        // a single `EventRecord` with `Balances::Transfer` and no topics
+        let events = (
+            Compact(1u32),
+            system::Phase::ApplyExtrinsic(0),
+            1u8,
+            1u8,
+            (from.clone(), to.clone(), 100u128),
+            Vec::<Hash>::new(),
+        )
+            .encode();
+        node.emit_events(events);
+
+        let transfer: (AccountId, AccountId, Balance) = api
+            .wait_for_event("Balances", "Transfer", None, &events_out)
+            .unwrap();
+        assert_eq!(transfer, (from, to, 100));
+    }
+
+    #[cfg(feature = "ws-client")]
+    #[test]
+    fn api_follows_the_extrinsic_script() {
+        let node = MockNode::new(test_metadata()).set_block_hash(Hash::repeat_byte(9));
+        let api = api(&node);
+
+        node.push_extrinsic_script(vec![
+            XtStatus::Ready,
+            XtStatus::Broadcast,
+            XtStatus::InBlock,
+        ]);
+        assert_eq!(
+            api.send_extrinsic("0x01".to_string(), XtStatus::InBlock)
+                .unwrap(),
+            Some(Hash::repeat_byte(9))
+        );
+
+        node.push_extrinsic_script(vec![XtStatus::Ready, XtStatus::Broadcast]);
+        assert!(matches!(
+            api.send_extrinsic("0x02".to_string(), XtStatus::InBlock),
+            Err(ApiClientError::Disconnected(_))
+        ));
+
+        node.push_extrinsic_script(vec![XtStatus::Ready, XtStatus::Future]);
+        assert_eq!(
+            api.send_extrinsic("0x03".to_string(), XtStatus::Finalized)
+                .unwrap(),
+            None
+        );
+        assert_eq!(node.submitted_extrinsics(), vec!["0x01", "0x02", "0x03"]);
+    }
+}
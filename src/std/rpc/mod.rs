@@ -14,15 +14,57 @@
    limitations under the License.
 
 */
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::std::{ApiClientError, ApiResult};
 
 #[cfg(feature = "ws-client")]
-pub use ws_client::{EventsError, WsRpcClient};
+pub use ws_client::{
+    EventsError, HandshakeConfig, ReconnectPolicy, Reconnected, TlsConfig, WsRpcClient,
+};
 
 #[cfg(feature = "ws-client")]
 pub mod ws_client;
 
+#[cfg(feature = "http-client")]
+pub use http_client::HttpRpcClient;
+
+#[cfg(feature = "http-client")]
+pub mod http_client;
+
+#[cfg(feature = "async")]
+pub use async_ws_client::AsyncWsRpcClient;
+
+#[cfg(feature = "async")]
+pub mod async_ws_client;
+
+pub use author::{ExtrinsicOrHash, PendingExtrinsic, PoolNonces};
+pub use cancel::CancelToken;
+pub use failover::FailoverRpcClient;
+pub use methods::RpcMethod;
+pub use middleware::{
+    LoggingRpcClient, MetricsRpcClient, RetryPolicy, RetryRpcClient, RpcClientExt, RpcMetrics,
+};
+pub use mock_node::MockNode;
+pub use offchain::StorageKind;
+pub use rate_limit::{OnLimit, Quota, RateLimit, RateLimitedRpcClient};
+pub use recording::{Record, RecordingRpcClient, ReplayRpcClient};
+pub use system::{ChainProperties, ChainType, Health, PeerInfo, SyncState};
+
+pub mod author;
+pub mod cancel;
+pub mod failover;
 pub mod json_req;
+pub mod methods;
+pub mod middleware;
+pub mod mock_node;
+pub mod offchain;
+pub mod rate_limit;
+pub mod recording;
+pub mod system;
 
 #[derive(Debug, thiserror::Error)]
 pub enum RpcClientError {
@@ -34,6 +76,116 @@ pub enum RpcClientError {
     Send(#[from] std::sync::mpsc::SendError<String>),
 }
 
+/// Returns the `result` of a JSON-RPC response, or its `error` as `ApiClientError::Rpc`.
+pub fn response_result(response: &Value) -> ApiResult<String> {
+    match response["error"].as_object() {
+        Some(error) => Err(rpc_error(error)),
+        None => Ok(response["result"].to_string()),
+    }
+}
+
+fn rpc_error(error: &Map<String, Value>) -> ApiClientError {
+    ApiClientError::Rpc {
+        code: error.get("code").and_then(|c| c.as_i64()).unwrap_or(-1),
+        message: error
+            .get("message")
+            .and_then(|m| m.as_str())
+            .unwrap_or("")
+            .to_string(),
+        data: error.get("data").filter(|d| !d.is_null()).map(|d| match d {
+            Value::String(data) => data.clone(),
+            other => other.to_string(),
+        }),
+    }
+}
+
+/// Extracts the results of a batch response in the order of `ids`, the ids of the batched
+/// requests. A `null` result is returned as `None`, the first error of the batch or an error
+/// rejecting the whole batch as `ApiClientError::Rpc`.
+///
+/// Fails if the response does not answer exactly the requests of `ids`.
+pub fn batch_results(response: Value, ids: &[u32]) -> ApiResult<Vec<Option<String>>> {
+    let responses = match response {
+        Value::Array(responses) => responses,
+        other => {
+            response_result(&other)?;
+            return Err(ApiClientError::RpcClient(format!(
+                "expected a batch response, got: {}",
+                other
+            )));
+        }
+    };
+
+    let mut by_id = HashMap::new();
+    for r in responses {
+        response_result(&r)?;
+        let id = match single_response_id(&r) {
+            Some(id) if ids.contains(&id) => id,
+            _ => {
+                return Err(ApiClientError::RpcClient(format!(
+                    "batch response to an unknown request: {}",
+                    r
+                )))
+            }
+        };
+        if by_id.insert(id, r).is_some() {
+            return Err(ApiClientError::RpcClient(format!(
+                "batch response answers the request with id {} twice",
+                id
+            )));
+        }
+    }
+
+    ids.iter()
+        .map(|id| match by_id.remove(id) {
+            Some(r) => match &r["result"] {
+                Value::Null => Ok(None),
+                result => Ok(Some(result.to_string())),
+            },
+            None => Err(ApiClientError::RpcClient(format!(
+                "batch response misses the request with id {}",
+                id
+            ))),
+        })
+        .collect()
+}
+
+fn single_response_id(value: &Value) -> Option<u32> {
+    match &value["id"] {
+        Value::String(id) => id.parse().ok(),
+        Value::Number(id) => id.as_u64().map(|id| id as u32),
+        _ => None,
+    }
+}
+
+/// Returns the id of the request a response belongs to.
+#[cfg(any(feature = "ws-client", feature = "async"))]
+pub(crate) fn response_id(value: &Value) -> Option<u32> {
+    match value {
+        // a batch response belongs to the lowest id, which is the one of its first request
+        Value::Array(responses) => responses.iter().filter_map(response_id).min(),
+        _ => single_response_id(value),
+    }
+}
+
+/// Returns the method that cancels a subscription opened with `method`.
+#[cfg(any(feature = "ws-client", feature = "async"))]
+pub(crate) fn unsubscribe_method(method: &str) -> Option<&'static str> {
+    match method {
+        "state_subscribeStorage" => Some("state_unsubscribeStorage"),
+        "chain_subscribeFinalizedHeads" => Some("chain_unsubscribeFinalizedHeads"),
+        "chain_subscribeNewHeads" => Some("chain_unsubscribeNewHeads"),
+        "author_submitAndWatchExtrinsic" => Some("author_unwatchExtrinsic"),
+        _ => None,
+    }
+}
+
+/// Whether sending a request of `method` twice has the same effect as sending it once. Not the
+/// case for `author_*` requests, like `author_rotateKeys`, and for offchain storage writes.
+pub(crate) fn is_idempotent(method: &str) -> bool {
+    !method.starts_with("author_") && method != "offchain_localStorageSet"
+}
+
 #[derive(Debug, PartialEq)]
 pub enum XtStatus {
     Finalized,
@@ -56,3 +208,111 @@ pub struct ReadProof<Hash> {
     /// A proof used to prove that storage entries are included in the storage trie
     pub proof: Vec<sp_core::Bytes>,
 }
+
+/// Methods served by the node, as reported by `rpc_methods`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RpcMethodList {
+    pub version: u32,
+    pub methods: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn batch_results_are_ordered_by_id() {
+        let response = json!([
+            {"jsonrpc": "2.0", "result": "0x02", "id": "2"},
+            {"jsonrpc": "2.0", "result": null, "id": "1"},
+            {"jsonrpc": "2.0", "result": "0x00", "id": "0"},
+        ]);
+        assert_eq!(
+            batch_results(response, &[0, 1, 2]).unwrap(),
+            vec![
+                Some("\"0x00\"".to_string()),
+                None,
+                Some("\"0x02\"".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn batch_fails_on_missing_or_unknown_ids() {
+        let response = json!([
+            {"jsonrpc": "2.0", "result": "0x00", "id": "0"},
+            {"jsonrpc": "2.0", "result": "0x01", "id": "1"},
+        ]);
+        assert!(matches!(
+            batch_results(response.clone(), &[0, 1, 2]),
+            Err(ApiClientError::RpcClient(_))
+        ));
+        assert!(matches!(
+            batch_results(response, &[0]),
+            Err(ApiClientError::RpcClient(_))
+        ));
+
+        let response = json!([
+            {"jsonrpc": "2.0", "result": "0x00", "id": "0"},
+            {"jsonrpc": "2.0", "result": "0x01", "id": "x"},
+        ]);
+        assert!(matches!(
+            batch_results(response, &[0, 1]),
+            Err(ApiClientError::RpcClient(_))
+        ));
+    }
+
+    #[test]
+    fn batch_fails_on_rejected_batch() {
+        let response = json!({
+            "jsonrpc": "2.0",
+            "error": {"code": -32600, "message": "Invalid request"},
+            "id": null
+        });
+        assert!(matches!(
+            batch_results(response, &[0, 1]),
+            Err(ApiClientError::Rpc { code: -32600, .. })
+        ));
+    }
+
+    #[test]
+    fn errors_are_returned_as_rpc_errors() {
+        let response = json!({
+            "jsonrpc": "2.0",
+            "error": {"code": 1010, "message": "Invalid Transaction", "data": "Bad Signature"},
+            "id": "1"
+        });
+        match response_result(&response) {
+            Err(ApiClientError::Rpc {
+                code,
+                message,
+                data,
+            }) => {
+                assert_eq!(code, 1010);
+                assert_eq!(message, "Invalid Transaction");
+                assert_eq!(data.as_deref(), Some("Bad Signature"));
+            }
+            other => panic!("expected rpc error, got {:?}", other),
+        }
+
+        let response = json!({"jsonrpc": "2.0", "result": null, "id": "1"});
+        assert_eq!(response_result(&response).unwrap(), "null");
+    }
+
+    #[test]
+    fn batch_fails_on_error_response() {
+        let response = json!([
+            {"jsonrpc": "2.0", "result": "0x00", "id": "0"},
+            {"jsonrpc": "2.0", "error": {"code": -32601, "message": "Method not found"}, "id": "1"},
+        ]);
+        assert!(matches!(
+            batch_results(response, &[0, 1]),
+            Err(ApiClientError::Rpc {
+                code: -32601,
+                data: None,
+                ..
+            })
+        ));
+    }
+}
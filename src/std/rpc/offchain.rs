@@ -0,0 +1,48 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! Parameters of the `offchain_*` RPCs.
+
+use serde::{Deserialize, Serialize};
+
+/// Offchain storage of the node, as used by offchain workers through `sp_io::offchain`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum StorageKind {
+    /// Storage shared by all offchain workers and kept across forks.
+    Persistent,
+    /// Storage of a single offchain worker run. Nodes don't expose it over RPC, requests for it
+    /// fail.
+    Local,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn storage_kinds_are_serialized_like_the_node_expects() {
+        assert_eq!(
+            serde_json::to_string(&StorageKind::Persistent).unwrap(),
+            "\"PERSISTENT\""
+        );
+        assert_eq!(
+            serde_json::to_string(&StorageKind::Local).unwrap(),
+            "\"LOCAL\""
+        );
+    }
+}
@@ -0,0 +1,555 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! Client-side rate limiting, so that a busy sender does not get throttled or flood the node.
+//!
+//! ```no_run
+//! use substrate_api_client::rpc::{Quota, RateLimit, RpcClientExt, WsRpcClient};
+//!
+//! let client = WsRpcClient::new("ws://127.0.0.1:9944").with_rate_limit(
+//!     RateLimit::default()
+//!         .set_submissions(Quota::per_second(10))
+//!         .set_max_in_flight(4),
+//! );
+//! ```
+
+use std::fmt;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+#[cfg(feature = "ws-client")]
+use std::sync::mpsc::Sender as ThreadOut;
+
+#[cfg(feature = "ws-client")]
+use crate::std::rpc::ws_client::{Reconnected, Subscriber};
+use crate::std::{ApiClientError, ApiResult, RpcClient, XtStatus};
+use crate::Hash;
+
+/// A token bucket: `burst` requests at once, refilled with `per_second` requests per second.
+///
+/// A quota with a `burst` of zero denies all requests, as does one that runs out of tokens and
+/// is not refilled. They fail with `ApiClientError::RateLimited` also with `OnLimit::Block`.
+///
+/// Every request of a batch takes a token. A batch larger than the burst takes its tokens in
+/// chunks of the burst with `OnLimit::Block`, and is rejected with `OnLimit::Reject`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quota {
+    pub burst: u32,
+    pub per_second: f64,
+}
+
+impl Quota {
+    /// `n` requests per second, `n` of which may be sent at once. Zero denies all requests.
+    pub fn per_second(n: u32) -> Self {
+        Self {
+            burst: n,
+            per_second: f64::from(n),
+        }
+    }
+
+    pub fn set_burst(mut self, burst: u32) -> Self {
+        self.burst = burst;
+        self
+    }
+}
+
+/// What happens to a request that exceeds a limit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OnLimit {
+    /// Wait until the request is within the limits.
+    Block,
+    /// Fail with `ApiClientError::RateLimited`.
+    Reject,
+}
+
+/// Limits of a `RateLimitedRpcClient`. Nothing is limited by default.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimit {
+    /// Quota of all requests except `author_*`.
+    pub reads: Option<Quota>,
+    /// Quota of extrinsic submissions and other `author_*` requests.
+    pub submissions: Option<Quota>,
+    /// Requests waiting for their response at the same time. A batch counts as one.
+    pub max_in_flight: Option<usize>,
+    pub on_limit: OnLimit,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            reads: None,
+            submissions: None,
+            max_in_flight: None,
+            on_limit: OnLimit::Block,
+        }
+    }
+}
+
+impl RateLimit {
+    pub fn set_reads(mut self, quota: Quota) -> Self {
+        self.reads = Some(quota);
+        self
+    }
+
+    pub fn set_submissions(mut self, quota: Quota) -> Self {
+        self.submissions = Some(quota);
+        self
+    }
+
+    pub fn set_max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = Some(max_in_flight);
+        self
+    }
+
+    pub fn set_on_limit(mut self, on_limit: OnLimit) -> Self {
+        self.on_limit = on_limit;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Class {
+    Read,
+    Submission,
+}
+
+impl Class {
+    fn of(jsonreq: &Value) -> Self {
+        match jsonreq["method"].as_str() {
+            Some(method) if method.starts_with("author_") => Class::Submission,
+            _ => Class::Read,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Class::Read => "reads",
+            Class::Submission => "submissions",
+        }
+    }
+}
+
+/// Source of time of a limiter, replaced in tests.
+trait Clock: fmt::Debug + Send + Sync {
+    fn now(&self) -> Instant;
+    fn sleep(&self, duration: Duration);
+}
+
+#[derive(Debug)]
+struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration)
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    quota: Quota,
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl TokenBucket {
+    fn new(quota: Quota, now: Instant) -> Self {
+        Self {
+            tokens: f64::from(quota.burst),
+            quota,
+            refilled: now,
+        }
+    }
+
+    /// Takes `n` tokens, or returns how long to wait until they are available. `None` if they
+    /// never will be, e.g. because `n` is larger than the burst.
+    fn take(&mut self, n: u32, now: Instant) -> Result<(), Option<Duration>> {
+        if n > self.quota.burst {
+            return Err(None);
+        }
+        let refill = now.duration_since(self.refilled).as_secs_f64() * self.quota.per_second;
+        self.tokens = (self.tokens + refill).min(f64::from(self.quota.burst));
+        self.refilled = now;
+
+        let n = f64::from(n);
+        if self.tokens >= n {
+            self.tokens -= n;
+            Ok(())
+        } else if self.quota.per_second > 0.0 {
+            Err(Some(Duration::from_secs_f64(
+                (n - self.tokens) / self.quota.per_second,
+            )))
+        } else {
+            Err(None)
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Limiter {
+    limit: RateLimit,
+    reads: Option<Mutex<TokenBucket>>,
+    submissions: Option<Mutex<TokenBucket>>,
+    in_flight: Mutex<usize>,
+    released: Condvar,
+    clock: Arc<dyn Clock>,
+}
+
+impl Limiter {
+    fn take_tokens(&self, class: Class, n: u32) -> ApiResult<()> {
+        let bucket = match class {
+            Class::Read => &self.reads,
+            Class::Submission => &self.submissions,
+        };
+        let bucket = match bucket {
+            Some(bucket) => bucket,
+            None => return Ok(()),
+        };
+        // a batch larger than the burst only fits in chunks
+        let chunk = match self.limit.on_limit {
+            OnLimit::Block => bucket.lock().unwrap().quota.burst.max(1),
+            OnLimit::Reject => n,
+        };
+        let mut left = n;
+        while left > 0 {
+            let n = left.min(chunk);
+            self.wait_for_tokens(class, bucket, n)?;
+            left -= n;
+        }
+        Ok(())
+    }
+
+    fn wait_for_tokens(&self, class: Class, bucket: &Mutex<TokenBucket>, n: u32) -> ApiResult<()> {
+        loop {
+            let wait = match bucket.lock().unwrap().take(n, self.clock.now()) {
+                Ok(()) => return Ok(()),
+                Err(wait) => wait,
+            };
+            match (self.limit.on_limit, wait) {
+                (OnLimit::Block, Some(wait)) => self.clock.sleep(wait),
+                // the tokens never come back
+                _ => return Err(ApiClientError::RateLimited(class.name().into())),
+            }
+        }
+    }
+
+    fn enter(&self) -> ApiResult<InFlight> {
+        if let Some(max) = self.limit.max_in_flight {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            while *in_flight >= max {
+                if self.limit.on_limit == OnLimit::Reject {
+                    return Err(ApiClientError::RateLimited("in-flight requests".into()));
+                }
+                in_flight = self.released.wait(in_flight).unwrap();
+            }
+            *in_flight += 1;
+        }
+        Ok(InFlight(self))
+    }
+}
+
+/// Releases an in-flight slot when dropped.
+struct InFlight<'a>(&'a Limiter);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        if self.0.limit.max_in_flight.is_some() {
+            *self.0.in_flight.lock().unwrap() -= 1;
+            self.0.released.notify_one();
+        }
+    }
+}
+
+/// Limits the request rate and the in-flight requests of a client. Clones share the limits.
+///
+/// Subscriptions are passed through without limits.
+#[derive(Debug, Clone)]
+pub struct RateLimitedRpcClient<Client> {
+    client: Client,
+    limiter: Arc<Limiter>,
+}
+
+impl<Client> RateLimitedRpcClient<Client> {
+    pub fn new(client: Client, limit: RateLimit) -> Self {
+        Self::with_clock(client, limit, Arc::new(SystemClock))
+    }
+
+    fn with_clock(client: Client, limit: RateLimit, clock: Arc<dyn Clock>) -> Self {
+        let now = clock.now();
+        let bucket = |quota: Option<Quota>| quota.map(|q| Mutex::new(TokenBucket::new(q, now)));
+        let limiter = Limiter {
+            reads: bucket(limit.reads.clone()),
+            submissions: bucket(limit.submissions.clone()),
+            limit,
+            in_flight: Mutex::new(0),
+            released: Condvar::new(),
+            clock,
+        };
+        Self {
+            client,
+            limiter: Arc::new(limiter),
+        }
+    }
+}
+
+impl<Client: RpcClient> RpcClient for RateLimitedRpcClient<Client> {
+    fn get_request(&self, jsonreq: Value) -> ApiResult<String> {
+        self.limiter.take_tokens(Class::of(&jsonreq), 1)?;
+        let _in_flight = self.limiter.enter()?;
+        self.client.get_request(jsonreq)
+    }
+
+    fn get_batch(&self, jsonreqs: Vec<Value>) -> ApiResult<Vec<Option<String>>> {
+        let submissions = jsonreqs
+            .iter()
+            .filter(|req| Class::of(req) == Class::Submission)
+            .count() as u32;
+        let reads = jsonreqs.len() as u32 - submissions;
+        if reads > 0 {
+            self.limiter.take_tokens(Class::Read, reads)?;
+        }
+        if submissions > 0 {
+            self.limiter.take_tokens(Class::Submission, submissions)?;
+        }
+        let _in_flight = self.limiter.enter()?;
+        self.client.get_batch(jsonreqs)
+    }
+
+    fn send_extrinsic(&self, xthex_prefixed: String, exit_on: XtStatus) -> ApiResult<Option<Hash>> {
+        self.watch_extrinsic(xthex_prefixed, exit_on, &mut |_| {})
+    }
+
+    fn watch_extrinsic(
+        &self,
+        xthex_prefixed: String,
+        exit_on: XtStatus,
+        on_status: &mut dyn FnMut(&Value),
+    ) -> ApiResult<Option<Hash>> {
+        self.limiter.take_tokens(Class::Submission, 1)?;
+        let _in_flight = self.limiter.enter()?;
+        self.client
+            .watch_extrinsic(xthex_prefixed, exit_on, on_status)
+    }
+}
+
+#[cfg(feature = "ws-client")]
+impl<Client: Subscriber> Subscriber for RateLimitedRpcClient<Client> {
+    fn start_subscriber(&self, json_req: String, result_in: ThreadOut<String>) -> ApiResult<()> {
+        self.client.start_subscriber(json_req, result_in)
+    }
+
+    fn start_resumable_subscriber(
+        &self,
+        json_req: String,
+        result_in: ThreadOut<String>,
+        reconnected_in: ThreadOut<Reconnected>,
+    ) -> ApiResult<()> {
+        self.client
+            .start_resumable_subscriber(json_req, result_in, reconnected_in)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::std::rpc::json_req;
+    use std::sync::mpsc::{channel, Receiver, Sender};
+
+    /// Only moves when the limiter sleeps or the test advances it.
+    #[derive(Debug)]
+    struct ManualClock(Mutex<Instant>);
+
+    impl ManualClock {
+        fn elapsed_since(&self, start: Instant) -> Duration {
+            self.now().duration_since(start)
+        }
+
+        fn advance(&self, duration: Duration) {
+            *self.0.lock().unwrap() += duration;
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn now(&self) -> Instant {
+            *self.0.lock().unwrap()
+        }
+
+        fn sleep(&self, duration: Duration) {
+            self.advance(duration)
+        }
+    }
+
+    /// Answers right away, unless it has a gate. Then every request reports that it entered
+    /// the client and waits until the gate is closed.
+    #[derive(Clone, Default)]
+    struct TestClient {
+        gate: Option<(Sender<()>, Arc<Mutex<Receiver<()>>>)>,
+    }
+
+    impl RpcClient for TestClient {
+        fn get_request(&self, _: Value) -> ApiResult<String> {
+            if let Some((entered, gate)) = &self.gate {
+                entered.send(()).unwrap();
+                // only returns once the gate is closed
+                let _ = gate.lock().unwrap().recv();
+            }
+            Ok("null".to_string())
+        }
+
+        fn send_extrinsic(&self, _: String, _: XtStatus) -> ApiResult<Option<Hash>> {
+            Ok(None)
+        }
+    }
+
+    fn client(limit: RateLimit) -> (RateLimitedRpcClient<TestClient>, Arc<ManualClock>) {
+        let clock = Arc::new(ManualClock(Mutex::new(Instant::now())));
+        let client = RateLimitedRpcClient::with_clock(TestClient::default(), limit, clock.clone());
+        (client, clock)
+    }
+
+    fn is_rate_limited(res: ApiResult<String>) -> bool {
+        matches!(res, Err(ApiClientError::RateLimited(_)))
+    }
+
+    #[test]
+    fn reads_and_submissions_have_separate_quotas() {
+        let (client, _) = client(
+            RateLimit::default()
+                .set_submissions(Quota::per_second(1))
+                .set_on_limit(OnLimit::Reject),
+        );
+        client
+            .send_extrinsic("0x00".to_string(), XtStatus::Ready)
+            .unwrap();
+        assert!(matches!(
+            client.send_extrinsic("0x00".to_string(), XtStatus::Ready),
+            Err(ApiClientError::RateLimited(_))
+        ));
+        assert!(is_rate_limited(
+            client.get_request(json_req::author_submit_extrinsic("0x00"))
+        ));
+        for _ in 0..10 {
+            client.get_request(json_req::system_health()).unwrap();
+        }
+    }
+
+    #[test]
+    fn tokens_are_refilled_over_time() {
+        let (client, clock) = client(
+            RateLimit::default()
+                .set_reads(Quota::per_second(2))
+                .set_on_limit(OnLimit::Reject),
+        );
+        for _ in 0..2 {
+            client.get_request(json_req::system_health()).unwrap();
+        }
+        assert!(is_rate_limited(
+            client.get_request(json_req::system_health())
+        ));
+
+        clock.advance(Duration::from_millis(500));
+        client.get_request(json_req::system_health()).unwrap();
+        assert!(is_rate_limited(
+            client.get_request(json_req::system_health())
+        ));
+    }
+
+    #[test]
+    fn blocking_waits_for_tokens() {
+        let (client, clock) =
+            client(RateLimit::default().set_reads(Quota::per_second(20).set_burst(1)));
+        let start = clock.now();
+        for _ in 0..3 {
+            client.get_request(json_req::system_health()).unwrap();
+        }
+        let waited = clock.elapsed_since(start).as_secs_f64();
+        assert!((waited - 0.1).abs() < 1e-6, "waited {}s", waited);
+    }
+
+    #[test]
+    fn batches_larger_than_the_burst_are_charged_in_full() {
+        let (blocking, clock) = client(RateLimit::default().set_reads(Quota::per_second(10)));
+        let start = clock.now();
+        let batch = vec![json_req::system_health(); 25];
+        assert_eq!(blocking.get_batch(batch).unwrap().len(), 25);
+        let waited = clock.elapsed_since(start).as_secs_f64();
+        assert!((waited - 1.5).abs() < 1e-6, "waited {}s", waited);
+
+        let (rejecting, _) = client(
+            RateLimit::default()
+                .set_reads(Quota::per_second(10))
+                .set_on_limit(OnLimit::Reject),
+        );
+        assert!(matches!(
+            rejecting.get_batch(vec![json_req::system_health(); 11]),
+            Err(ApiClientError::RateLimited(_))
+        ));
+        // the rejected batch took no tokens
+        let batch = vec![json_req::system_health(); 10];
+        assert_eq!(rejecting.get_batch(batch).unwrap().len(), 10);
+    }
+
+    #[test]
+    fn zero_quotas_deny_all_requests() {
+        let (client, clock) = client(
+            RateLimit::default()
+                .set_reads(Quota::per_second(0))
+                .set_submissions(Quota::per_second(10).set_burst(0)),
+        );
+        let start = clock.now();
+        assert!(is_rate_limited(
+            client.get_request(json_req::system_health())
+        ));
+        assert!(matches!(
+            client.send_extrinsic("0x00".to_string(), XtStatus::Ready),
+            Err(ApiClientError::RateLimited(_))
+        ));
+        // the requests failed without blocking
+        assert_eq!(clock.elapsed_since(start), Duration::default());
+    }
+
+    #[test]
+    fn in_flight_requests_are_capped() {
+        let (entered_in, entered_out) = channel();
+        let (gate_in, gate_out) = channel::<()>();
+        let client = RateLimitedRpcClient::new(
+            TestClient {
+                gate: Some((entered_in, Arc::new(Mutex::new(gate_out)))),
+            },
+            RateLimit::default()
+                .set_max_in_flight(1)
+                .set_on_limit(OnLimit::Reject),
+        );
+        let busy = client.clone();
+        let handle = thread::spawn(move || busy.get_request(json_req::system_health()));
+        entered_out.recv().unwrap();
+
+        assert!(is_rate_limited(
+            client.get_request(json_req::system_health())
+        ));
+
+        drop(gate_in);
+        handle.join().unwrap().unwrap();
+        client.get_request(json_req::system_health()).unwrap();
+    }
+}
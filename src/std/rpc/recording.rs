@@ -0,0 +1,579 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! Records the traffic of a client to a file and serves it back without a node.
+//!
+//! A recording is a JSON lines file with one [`Record`] per line. Requests are matched by method
+//! and params, their ids are ignored. If the same request was recorded several times, the
+//! responses are served in the recorded order and the last one is repeated.
+
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use log::error;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[cfg(feature = "ws-client")]
+use std::sync::mpsc::{channel, Sender as ThreadOut};
+#[cfg(feature = "ws-client")]
+use std::thread;
+
+#[cfg(feature = "ws-client")]
+use crate::std::rpc::ws_client::{Reconnected, Subscriber};
+#[cfg(feature = "ws-client")]
+use crate::std::rpc::RpcClientError;
+use crate::std::{ApiClientError, ApiResult, RpcClient, XtStatus};
+use crate::Hash;
+
+/// One line of a recording.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum Record {
+    Request {
+        request: Value,
+        result: Result<String, RecordedError>,
+    },
+    Batch {
+        requests: Vec<Value>,
+        result: Result<Vec<Option<String>>, RecordedError>,
+    },
+    /// A watched extrinsic: the status updates of the node in the order they were sent, e.g.
+    /// `"ready"` or `{"inBlock": "0x…"}`, and the outcome of the watch.
+    Extrinsic {
+        extrinsic: String,
+        exit_on: String,
+        #[serde(default)]
+        statuses: Vec<Value>,
+        result: Result<Option<Hash>, RecordedError>,
+    },
+    /// A message of the subscription opened with `request`.
+    Notification { request: Value, message: String },
+}
+
+/// A recorded error. Node errors are replayed as they were, all others as
+/// `ApiClientError::RpcClient`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RecordedError {
+    Rpc {
+        code: i64,
+        message: String,
+        data: Option<String>,
+    },
+    Other(String),
+}
+
+impl From<&ApiClientError> for RecordedError {
+    fn from(error: &ApiClientError) -> Self {
+        match error {
+            ApiClientError::Rpc {
+                code,
+                message,
+                data,
+            } => RecordedError::Rpc {
+                code: *code,
+                message: message.clone(),
+                data: data.clone(),
+            },
+            other => RecordedError::Other(other.to_string()),
+        }
+    }
+}
+
+impl From<RecordedError> for ApiClientError {
+    fn from(error: RecordedError) -> Self {
+        match error {
+            RecordedError::Rpc {
+                code,
+                message,
+                data,
+            } => ApiClientError::Rpc {
+                code,
+                message,
+                data,
+            },
+            RecordedError::Other(message) => ApiClientError::RpcClient(message),
+        }
+    }
+}
+
+/// Wraps a client and appends every request and its response to a recording.
+///
+/// Failing to write the recording is logged, the requests themselves are not affected.
+#[derive(Debug, Clone)]
+pub struct RecordingRpcClient<Client> {
+    client: Client,
+    file: Arc<Mutex<File>>,
+}
+
+impl<Client> RecordingRpcClient<Client> {
+    /// Records to `path`. An existing file is overwritten.
+    pub fn new<P: AsRef<Path>>(client: Client, path: P) -> io::Result<Self> {
+        Ok(Self {
+            client,
+            file: Arc::new(Mutex::new(File::create(path)?)),
+        })
+    }
+
+    fn record(&self, record: &Record) {
+        write_record(&self.file, record)
+    }
+}
+
+impl<Client: RpcClient> RpcClient for RecordingRpcClient<Client> {
+    fn get_request(&self, jsonreq: Value) -> ApiResult<String> {
+        let request = normalize(&jsonreq);
+        let result = self.client.get_request(jsonreq);
+        self.record(&Record::Request {
+            request,
+            result: recorded(&result),
+        });
+        result
+    }
+
+    fn get_batch(&self, jsonreqs: Vec<Value>) -> ApiResult<Vec<Option<String>>> {
+        let requests = jsonreqs.iter().map(normalize).collect();
+        let result = self.client.get_batch(jsonreqs);
+        self.record(&Record::Batch {
+            requests,
+            result: recorded(&result),
+        });
+        result
+    }
+
+    fn send_extrinsic(&self, xthex_prefixed: String, exit_on: XtStatus) -> ApiResult<Option<Hash>> {
+        self.watch_extrinsic(xthex_prefixed, exit_on, &mut |_| {})
+    }
+
+    fn watch_extrinsic(
+        &self,
+        xthex_prefixed: String,
+        exit_on: XtStatus,
+        on_status: &mut dyn FnMut(&Value),
+    ) -> ApiResult<Option<Hash>> {
+        let extrinsic = xthex_prefixed.clone();
+        let exit_on_name = format!("{:?}", exit_on);
+        let mut statuses = Vec::new();
+        let result = self
+            .client
+            .watch_extrinsic(xthex_prefixed, exit_on, &mut |status| {
+                statuses.push(status.clone());
+                on_status(status)
+            });
+        self.record(&Record::Extrinsic {
+            extrinsic,
+            exit_on: exit_on_name,
+            statuses,
+            result: recorded(&result),
+        });
+        result
+    }
+}
+
+#[cfg(feature = "ws-client")]
+impl<Client: Subscriber> RecordingRpcClient<Client> {
+    /// Returns the sender to hand to the wrapped client. Its messages are recorded and passed on
+    /// to `result_in`.
+    fn record_subscription(
+        &self,
+        json_req: &str,
+        result_in: ThreadOut<String>,
+    ) -> Result<ThreadOut<String>, ws::Error> {
+        let request: Value =
+            serde_json::from_str(json_req).map_err(|e| Box::new(RpcClientError::Serde(e)))?;
+        let request = normalize(&request);
+        let file = self.file.clone();
+        let (recorded_in, recorded_out) = channel::<String>();
+
+        thread::Builder::new()
+            .name("recording".to_owned())
+            .spawn(move || {
+                for message in recorded_out {
+                    write_record(
+                        &file,
+                        &Record::Notification {
+                            request: request.clone(),
+                            message: message.clone(),
+                        },
+                    );
+                    // dropping `recorded_out` ends the subscription of the wrapped client
+                    if result_in.send(message).is_err() {
+                        break;
+                    }
+                }
+            })?;
+        Ok(recorded_in)
+    }
+}
+
+#[cfg(feature = "ws-client")]
+impl<Client: Subscriber> Subscriber for RecordingRpcClient<Client> {
+    fn start_subscriber(&self, json_req: String, result_in: ThreadOut<String>) -> ApiResult<()> {
+        let recorded_in = self.record_subscription(&json_req, result_in)?;
+        self.client.start_subscriber(json_req, recorded_in)
+    }
+
+    fn start_resumable_subscriber(
+        &self,
+        json_req: String,
+        result_in: ThreadOut<String>,
+        reconnected_in: ThreadOut<Reconnected>,
+    ) -> ApiResult<()> {
+        let recorded_in = self.record_subscription(&json_req, result_in)?;
+        self.client
+            .start_resumable_subscriber(json_req, recorded_in, reconnected_in)
+    }
+}
+
+type RecordedWatch = (Vec<Value>, Result<Option<Hash>, RecordedError>);
+
+/// Serves a recording back instead of talking to a node.
+///
+/// A request that was not recorded fails with `ApiClientError::RpcClient`.
+#[derive(Debug, Default)]
+pub struct ReplayRpcClient {
+    requests: Mutex<HashMap<String, VecDeque<Result<String, RecordedError>>>>,
+    batches: Mutex<HashMap<String, VecDeque<Result<Vec<Option<String>>, RecordedError>>>>,
+    extrinsics: Mutex<HashMap<String, VecDeque<RecordedWatch>>>,
+    notifications: HashMap<String, Vec<String>>,
+}
+
+impl ReplayRpcClient {
+    pub fn new(records: Vec<Record>) -> Self {
+        let mut replay = Self::default();
+        for record in records {
+            match record {
+                Record::Request { request, result } => {
+                    push(&mut replay.requests, request.to_string(), result)
+                }
+                Record::Batch { requests, result } => push(
+                    &mut replay.batches,
+                    Value::from(requests).to_string(),
+                    result,
+                ),
+                Record::Extrinsic {
+                    extrinsic,
+                    exit_on,
+                    statuses,
+                    result,
+                } => push(
+                    &mut replay.extrinsics,
+                    extrinsic_key(&extrinsic, &exit_on),
+                    (statuses, result),
+                ),
+                Record::Notification { request, message } => replay
+                    .notifications
+                    .entry(request.to_string())
+                    .or_default()
+                    .push(message),
+            }
+        }
+        replay
+    }
+
+    /// Loads a recording written by `RecordingRpcClient`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut records = Vec::new();
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                records.push(serde_json::from_str(&line)?);
+            }
+        }
+        Ok(Self::new(records))
+    }
+}
+
+impl RpcClient for ReplayRpcClient {
+    fn get_request(&self, jsonreq: Value) -> ApiResult<String> {
+        let key = normalize(&jsonreq).to_string();
+        replay(&self.requests, &key)
+    }
+
+    fn get_batch(&self, jsonreqs: Vec<Value>) -> ApiResult<Vec<Option<String>>> {
+        let requests: Vec<Value> = jsonreqs.iter().map(normalize).collect();
+        replay(&self.batches, &Value::from(requests).to_string())
+    }
+
+    fn send_extrinsic(&self, xthex_prefixed: String, exit_on: XtStatus) -> ApiResult<Option<Hash>> {
+        self.watch_extrinsic(xthex_prefixed, exit_on, &mut |_| {})
+    }
+
+    /// Hands the recorded status updates to `on_status` before returning the recorded outcome.
+    fn watch_extrinsic(
+        &self,
+        xthex_prefixed: String,
+        exit_on: XtStatus,
+        on_status: &mut dyn FnMut(&Value),
+    ) -> ApiResult<Option<Hash>> {
+        let key = extrinsic_key(&xthex_prefixed, &format!("{:?}", exit_on));
+        let (statuses, result) = next(&self.extrinsics, &key)?;
+        for status in &statuses {
+            on_status(status);
+        }
+        result.map_err(ApiClientError::from)
+    }
+}
+
+#[cfg(feature = "ws-client")]
+impl Subscriber for ReplayRpcClient {
+    /// Sends all recorded messages of the subscription at once.
+    fn start_subscriber(&self, json_req: String, result_in: ThreadOut<String>) -> ApiResult<()> {
+        let request: Value = serde_json::from_str(&json_req)?;
+        let messages = self
+            .notifications
+            .get(&normalize(&request).to_string())
+            .cloned()
+            .unwrap_or_default();
+        for message in messages {
+            if result_in.send(message).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Removes the id, which differs between a recording and its replay.
+fn normalize(request: &Value) -> Value {
+    let mut request = request.clone();
+    if let Some(obj) = request.as_object_mut() {
+        obj.remove("id");
+    }
+    request
+}
+
+fn recorded<T: Clone>(result: &ApiResult<T>) -> Result<T, RecordedError> {
+    result
+        .as_ref()
+        .map(|r| r.clone())
+        .map_err(RecordedError::from)
+}
+
+fn write_record(file: &Mutex<File>, record: &Record) {
+    let line = match serde_json::to_string(record) {
+        Ok(line) => line,
+        Err(e) => {
+            error!("could not serialize record {:?}: {}", record, e);
+            return;
+        }
+    };
+    let mut file = file.lock().unwrap();
+    if let Err(e) = writeln!(file, "{}", line).and_then(|_| file.flush()) {
+        error!("could not write recording: {}", e);
+    }
+}
+
+fn extrinsic_key(extrinsic: &str, exit_on: &str) -> String {
+    format!("{}:{}", exit_on, extrinsic)
+}
+
+fn push<T>(queues: &mut Mutex<HashMap<String, VecDeque<T>>>, key: String, value: T) {
+    queues
+        .get_mut()
+        .unwrap()
+        .entry(key)
+        .or_default()
+        .push_back(value)
+}
+
+/// Returns the next recorded result for `key`. The last one is repeated.
+fn replay<T: Clone>(
+    queues: &Mutex<HashMap<String, VecDeque<Result<T, RecordedError>>>>,
+    key: &str,
+) -> ApiResult<T> {
+    next(queues, key)?.map_err(ApiClientError::from)
+}
+
+/// Returns the next recorded entry for `key`. The last one is repeated.
+fn next<T: Clone>(queues: &Mutex<HashMap<String, VecDeque<T>>>, key: &str) -> ApiResult<T> {
+    let mut queues = queues.lock().unwrap();
+    let queue = queues
+        .get_mut(key)
+        .ok_or_else(|| ApiClientError::RpcClient(format!("no recorded response for {}", key)))?;
+    let result = if queue.len() > 1 {
+        queue.pop_front()
+    } else {
+        queue.front().cloned()
+    };
+    Ok(result.expect("queues are never empty"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::std::rpc::json_req;
+    use crate::std::rpc::mock_node::{test_metadata, MockNode};
+    use serde_json::json;
+    use std::cell::Cell;
+
+    struct TestClient {
+        nonce: Cell<u32>,
+    }
+
+    impl RpcClient for TestClient {
+        fn get_request(&self, jsonreq: Value) -> ApiResult<String> {
+            match jsonreq["method"].as_str() {
+                Some("chain_getFinalizedHead") => {
+                    self.nonce.set(self.nonce.get() + 1);
+                    Ok(format!("\"0x0{}\"", self.nonce.get()))
+                }
+                _ => Err(ApiClientError::Rpc {
+                    code: -32601,
+                    message: "Method not found".to_string(),
+                    data: None,
+                }),
+            }
+        }
+
+        fn send_extrinsic(&self, _: String, _: XtStatus) -> ApiResult<Option<Hash>> {
+            Ok(Some(Hash::repeat_byte(1)))
+        }
+    }
+
+    fn recording_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!(
+            "substrate-api-client-{}-{}.jsonl",
+            name,
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn recorded_session_is_replayed() {
+        let path = recording_path("replay");
+        let client = RecordingRpcClient::new(
+            TestClient {
+                nonce: Cell::new(0),
+            },
+            &path,
+        )
+        .unwrap();
+
+        let head = json_req::chain_get_finalized_head();
+        assert_eq!(client.get_request(head.clone()).unwrap(), "\"0x01\"");
+        assert_eq!(client.get_request(head.clone()).unwrap(), "\"0x02\"");
+        assert!(client.get_request(json_req::system_health()).is_err());
+        assert_eq!(
+            client.get_batch(vec![head.clone(), head.clone()]).unwrap(),
+            vec![Some("\"0x03\"".to_string()), Some("\"0x04\"".to_string())]
+        );
+        assert_eq!(
+            client
+                .send_extrinsic("0x00".to_string(), XtStatus::InBlock)
+                .unwrap(),
+            Some(Hash::repeat_byte(1))
+        );
+
+        let replay = ReplayRpcClient::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // ids do not matter, the last response is repeated
+        let mut head_with_other_id = head.clone();
+        head_with_other_id["id"] = Value::from("42");
+        assert_eq!(replay.get_request(head_with_other_id).unwrap(), "\"0x01\"");
+        assert_eq!(replay.get_request(head.clone()).unwrap(), "\"0x02\"");
+        assert_eq!(replay.get_request(head.clone()).unwrap(), "\"0x02\"");
+        assert!(matches!(
+            replay.get_request(json_req::system_health()),
+            Err(ApiClientError::Rpc { code: -32601, .. })
+        ));
+        assert_eq!(
+            replay.get_batch(vec![head.clone(), head]).unwrap(),
+            vec![Some("\"0x03\"".to_string()), Some("\"0x04\"".to_string())]
+        );
+        assert_eq!(
+            replay
+                .send_extrinsic("0x00".to_string(), XtStatus::InBlock)
+                .unwrap(),
+            Some(Hash::repeat_byte(1))
+        );
+        assert!(matches!(
+            replay.send_extrinsic("0x00".to_string(), XtStatus::Finalized),
+            Err(ApiClientError::RpcClient(_))
+        ));
+    }
+
+    #[test]
+    fn extrinsic_statuses_are_replayed_in_order() {
+        let path = recording_path("statuses");
+        let node = MockNode::new(test_metadata());
+        node.push_extrinsic_script(vec![
+            XtStatus::Ready,
+            XtStatus::Broadcast,
+            XtStatus::InBlock,
+        ]);
+        let client = RecordingRpcClient::new(node, &path).unwrap();
+        let mut watched = Vec::new();
+        let block_hash = client
+            .watch_extrinsic("0x00".to_string(), XtStatus::InBlock, &mut |status| {
+                watched.push(status.clone())
+            })
+            .unwrap();
+
+        let replay = ReplayRpcClient::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut replayed = Vec::new();
+        assert_eq!(
+            replay
+                .watch_extrinsic("0x00".to_string(), XtStatus::InBlock, &mut |status| {
+                    replayed.push(status.clone())
+                })
+                .unwrap(),
+            block_hash
+        );
+        assert_eq!(
+            replayed,
+            vec![
+                json!("ready"),
+                json!({ "broadcast": [] }),
+                json!({ "inBlock": Hash::repeat_byte(1) })
+            ]
+        );
+        assert_eq!(replayed, watched);
+    }
+
+    #[cfg(feature = "ws-client")]
+    #[test]
+    fn notifications_are_replayed() {
+        let request = json_req::chain_subscribe_finalized_heads();
+        let replay = ReplayRpcClient::new(vec![
+            Record::Notification {
+                request: normalize(&request),
+                message: "first".to_string(),
+            },
+            Record::Notification {
+                request: normalize(&request),
+                message: "second".to_string(),
+            },
+        ]);
+
+        let (result_in, result_out) = channel();
+        replay
+            .start_subscriber(request.to_string(), result_in)
+            .unwrap();
+        assert_eq!(
+            result_out.iter().collect::<Vec<_>>(),
+            vec!["first", "second"]
+        );
+    }
+}
@@ -0,0 +1,138 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! Responses of the `system_*` RPCs.
+//!
+//! Added manually, like `ReadProof`, so we don't need sc-rpc-api.
+
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+
+use crate::Hash;
+
+/// Health of the node, as reported by `system_health`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Health {
+    pub peers: usize,
+    pub is_syncing: bool,
+    /// Whether the node should be connected to peers. Not the case for dev chains.
+    pub should_have_peers: bool,
+}
+
+/// A peer the node is connected to, as reported by `system_peers`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerInfo {
+    pub peer_id: String,
+    pub roles: String,
+    pub best_hash: Hash,
+    pub best_number: u32,
+}
+
+/// Type of the chain, as reported by `system_chainType`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ChainType {
+    Development,
+    Local,
+    Live,
+    Custom(String),
+}
+
+/// Sync progress of the node, as reported by `system_syncState`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncState {
+    pub starting_block: u32,
+    pub current_block: u32,
+    /// Unknown as long as the node has no peers.
+    pub highest_block: Option<u32>,
+}
+
+/// Chain properties from the chain spec, as reported by `system_properties`.
+///
+/// Chains with several tokens report one decimal and symbol per token.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainProperties {
+    pub ss58_format: Option<u16>,
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub token_decimals: Vec<u32>,
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub token_symbol: Vec<String>,
+    /// All other properties of the chain spec.
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+fn one_or_many<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany<T> {
+        One(T),
+        Many(Vec<T>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => vec![value],
+        OneOrMany::Many(values) => values,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn properties_accept_single_and_multiple_tokens() {
+        let properties: ChainProperties = serde_json::from_value(json!({
+            "ss58Format": 42,
+            "tokenDecimals": 12,
+            "tokenSymbol": "UNIT",
+            "isEthereum": false,
+        }))
+        .unwrap();
+        assert_eq!(properties.ss58_format, Some(42));
+        assert_eq!(properties.token_decimals, vec![12]);
+        assert_eq!(properties.token_symbol, vec!["UNIT".to_string()]);
+        assert_eq!(properties.other["isEthereum"], json!(false));
+
+        let properties: ChainProperties = serde_json::from_value(json!({
+            "tokenDecimals": [12, 18],
+            "tokenSymbol": ["KAR", "KUSD"],
+        }))
+        .unwrap();
+        assert_eq!(properties.ss58_format, None);
+        assert_eq!(properties.token_decimals, vec![12, 18]);
+
+        let properties: ChainProperties = serde_json::from_value(json!({})).unwrap();
+        assert_eq!(properties, ChainProperties::default());
+    }
+
+    #[test]
+    fn chain_types_are_decoded() {
+        let live: ChainType = serde_json::from_str("\"Live\"").unwrap();
+        assert_eq!(live, ChainType::Live);
+        let custom: ChainType = serde_json::from_str(r#"{"Custom":"Staging"}"#).unwrap();
+        assert_eq!(custom, ChainType::Custom("Staging".to_string()));
+    }
+}
@@ -1,103 +1,245 @@
 use std::sync::mpsc::channel;
-use std::sync::mpsc::Sender as ThreadOut;
-use std::thread;
+use std::sync::mpsc::{Receiver, Sender as ThreadOut};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use log::info;
 use serde_json::Value;
 use sp_core::H256 as Hash;
-use ws::{connect, Result as WsResult};
+use ws::Result as WsResult;
 
+use crate::std::rpc::cancel::{deadline, recv, CancelToken};
 use crate::std::rpc::json_req;
 use crate::std::rpc::ws_client::Subscriber;
 use crate::std::rpc::ws_client::{
-    on_extrinsic_msg_until_broadcast, on_extrinsic_msg_until_finalized,
-    on_extrinsic_msg_until_in_block, on_extrinsic_msg_until_ready, on_get_request_msg,
-    on_subscription_msg, OnMessageFn, RpcClient,
+    on_batch_msg, on_extrinsic_msg, on_extrinsic_msg_until_broadcast,
+    on_extrinsic_msg_until_finalized, on_extrinsic_msg_until_in_block,
+    on_extrinsic_msg_until_ready, on_get_request_msg, on_subscription_msg, parse_status,
+    HandshakeConfig, OnMessageFn, ReconnectPolicy, Reconnected, TlsConfig, WsConnection,
 };
+use crate::std::rpc::{batch_results, response_result};
 use crate::std::ApiClientError;
 use crate::std::ApiResult;
 use crate::std::FromHexString;
 use crate::std::RpcClient as RpcClientTrait;
 use crate::std::XtStatus;
 
+/// JSON-RPC client that sends all requests and subscriptions over one WebSocket connection.
+///
+/// The connection is opened on the first request and shared by all clones of the client. If it
+/// is closed, the next request opens a new one.
 #[derive(Debug, Clone)]
 pub struct WsRpcClient {
     url: String,
+    timeout: Option<Duration>,
+    reconnect: Option<ReconnectPolicy>,
+    handshake: HandshakeConfig,
+    connection: Arc<Mutex<Option<Arc<WsConnection>>>>,
 }
 
 impl WsRpcClient {
     pub fn new(url: &str) -> WsRpcClient {
         WsRpcClient {
             url: url.to_string(),
+            timeout: None,
+            reconnect: None,
+            handshake: HandshakeConfig::default(),
+            connection: Arc::new(Mutex::new(None)),
         }
     }
-}
 
-impl RpcClientTrait for WsRpcClient {
-    fn get_request(&self, jsonreq: Value) -> ApiResult<String> {
+    /// Sets the default timeout for all calls of this client. Without timeout, calls wait
+    /// forever for the node to answer.
+    pub fn set_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Re-establishes lost connections according to `policy` and re-issues active
+    /// subscriptions. Without policy, subscriptions end when the connection is lost.
+    pub fn set_reconnect_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect = Some(policy);
+        self
+    }
+
+    /// Sets the certificates used for `wss://` urls.
+    pub fn set_tls_config(mut self, tls: TlsConfig) -> Self {
+        self.handshake.tls = tls;
+        self
+    }
+
+    /// Sends `name: value` with the handshake, e.g. the API key of a hosted RPC provider.
+    /// Replaces an earlier header of the same name.
+    pub fn set_header(mut self, name: &str, value: &str) -> Self {
+        self.handshake
+            .headers
+            .retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+        self.handshake
+            .headers
+            .push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn get_request_with_timeout(
+        &self,
+        jsonreq: Value,
+        timeout: Option<Duration>,
+    ) -> ApiResult<String> {
+        let deadline = deadline(timeout);
         let (result_in, result_out) = channel();
-        self.get(jsonreq.to_string(), result_in)?;
+        let connection = self.connection()?;
+        let id = connection.request(&jsonreq.to_string(), result_in, on_get_request_msg)?;
 
-        let str = result_out.recv()?;
-        Ok(str)
+        let str = wait(&connection, id, &result_out, deadline, None)?;
+        response_result(&serde_json::from_str(&str)?)
     }
 
-    fn send_extrinsic(
+    /// Sends the extrinsic and watches it until `exit_on` is reached, `timeout` has passed or
+    /// `cancel` is cancelled. Giving up stops watching the extrinsic on the node.
+    pub fn send_extrinsic_with_timeout(
         &self,
+        xthex_prefixed: String,
+        exit_on: XtStatus,
+        timeout: Option<Duration>,
+        cancel: Option<&CancelToken>,
+    ) -> ApiResult<Option<sp_core::H256>> {
+        self.watch_extrinsic_with_timeout(xthex_prefixed, exit_on, timeout, cancel, &mut |_| {})
+    }
 
+    /// Like `send_extrinsic_with_timeout`, but hands every status update of the watch to
+    /// `on_status`, in the order the node sent them.
+    pub fn watch_extrinsic_with_timeout(
+        &self,
         xthex_prefixed: String,
         exit_on: XtStatus,
+        timeout: Option<Duration>,
+        cancel: Option<&CancelToken>,
+        on_status: &mut dyn FnMut(&Value),
     ) -> ApiResult<Option<sp_core::H256>> {
+        match exit_on {
+            XtStatus::Finalized | XtStatus::InBlock | XtStatus::Broadcast | XtStatus::Ready => {}
+            _ => return Err(ApiClientError::UnsupportedXtStatus(exit_on)),
+        }
+        let deadline = deadline(timeout);
         let jsonreq = json_req::author_submit_and_watch_extrinsic(&xthex_prefixed).to_string();
 
         let (result_in, result_out) = channel();
-        match exit_on {
-            XtStatus::Finalized => {
-                self.send_extrinsic_and_wait_until_finalized(jsonreq, result_in)?;
-                let res = result_out.recv()?;
-                info!("finalized: {}", res);
-                Ok(Some(Hash::from_hex(res)?))
+        let connection = self.connection()?;
+        let id = connection.request(&jsonreq, result_in, on_extrinsic_msg)?;
+        loop {
+            let msg = wait(&connection, id, &result_out, deadline, cancel)?;
+            let response: Value = serde_json::from_str(&msg)?;
+            let update = &response["params"]["result"];
+            if update.is_null() {
+                // the subscription id, or the error of the node
+                response_result(&response)?;
+                continue;
             }
-            XtStatus::InBlock => {
-                self.send_extrinsic_and_wait_until_in_block(jsonreq, result_in)?;
-                let res = result_out.recv()?;
-                info!("inBlock: {}", res);
-                Ok(Some(Hash::from_hex(res)?))
+            on_status(update);
+            let (status, hash) =
+                parse_status(&msg).map_err(|e| ApiClientError::RpcClient(e.to_string()))?;
+            let ended = matches!(status, XtStatus::Finalized | XtStatus::Future);
+            if !ended && status != exit_on {
+                continue;
             }
-            XtStatus::Broadcast => {
-                self.send_extrinsic_and_wait_until_broadcast(jsonreq, result_in)?;
-                let res = result_out.recv()?;
-                info!("broadcast: {}", res);
-                Ok(None)
+            info!("{:?}: {:?}", status, hash);
+            if !ended {
+                // the node keeps watching otherwise
+                connection.cancel(id);
             }
-            XtStatus::Ready => {
-                self.send_extrinsic(jsonreq, result_in)?;
-                let res = result_out.recv()?;
-                info!("ready: {}", res);
-                Ok(None)
-            }
-            _ => Err(ApiClientError::UnsupportedXtStatus(exit_on)),
+            return match (exit_on, hash) {
+                (XtStatus::Finalized, Some(hash)) | (XtStatus::InBlock, Some(hash)) => {
+                    Ok(Some(Hash::from_hex(hash)?))
+                }
+                _ => Ok(None),
+            };
         }
     }
 }
 
+/// Receives the result of the request `id`. Giving up drops the route of the request and
+/// cancels the subscription it opened on the node.
+fn wait(
+    connection: &WsConnection,
+    id: u32,
+    result_out: &Receiver<String>,
+    deadline: Option<Instant>,
+    cancel: Option<&CancelToken>,
+) -> ApiResult<String> {
+    recv(result_out, deadline, cancel).map_err(|e| {
+        connection.cancel(id);
+        e
+    })
+}
+
+impl RpcClientTrait for WsRpcClient {
+    fn get_request(&self, jsonreq: Value) -> ApiResult<String> {
+        self.get_request_with_timeout(jsonreq, self.timeout)
+    }
+
+    fn get_batch(&self, jsonreqs: Vec<Value>) -> ApiResult<Vec<Option<String>>> {
+        if jsonreqs.is_empty() {
+            return Ok(Vec::new());
+        }
+        let deadline = deadline(self.timeout);
+        let count = jsonreqs.len() as u32;
+        let (result_in, result_out) = channel();
+        let connection = self.connection()?;
+        let id = connection.request(
+            &json_req::batch(jsonreqs).to_string(),
+            result_in,
+            on_batch_msg,
+        )?;
+        let ids: Vec<u32> = (id..id + count).collect();
+
+        let str = wait(&connection, id, &result_out, deadline, None)?;
+        batch_results(serde_json::from_str(&str)?, &ids)
+    }
+
+    fn send_extrinsic(
+        &self,
+        xthex_prefixed: String,
+        exit_on: XtStatus,
+    ) -> ApiResult<Option<sp_core::H256>> {
+        self.send_extrinsic_with_timeout(xthex_prefixed, exit_on, self.timeout, None)
+    }
+
+    fn watch_extrinsic(
+        &self,
+        xthex_prefixed: String,
+        exit_on: XtStatus,
+        on_status: &mut dyn FnMut(&Value),
+    ) -> ApiResult<Option<sp_core::H256>> {
+        self.watch_extrinsic_with_timeout(xthex_prefixed, exit_on, self.timeout, None, on_status)
+    }
+}
+
 impl Subscriber for WsRpcClient {
-    fn start_subscriber(
+    fn start_subscriber(&self, json_req: String, result_in: ThreadOut<String>) -> ApiResult<()> {
+        self.start_subscriber(json_req, result_in)
+    }
+
+    fn start_resumable_subscriber(
         &self,
         json_req: String,
         result_in: ThreadOut<String>,
-    ) -> Result<(), ws::Error> {
-        self.start_subscriber(json_req, result_in)
+        reconnected_in: ThreadOut<Reconnected>,
+    ) -> ApiResult<()> {
+        self.subscribe(&json_req, result_in, Some(reconnected_in))
     }
 }
 
 impl WsRpcClient {
     pub fn get(&self, json_req: String, result_in: ThreadOut<String>) -> WsResult<()> {
-        self.start_rpc_client_thread(json_req, result_in, on_get_request_msg)
+        self.send_request(json_req, result_in, on_get_request_msg)
+    }
+
+    pub fn batch(&self, json_req: String, result_in: ThreadOut<String>) -> WsResult<()> {
+        self.send_request(json_req, result_in, on_batch_msg)
     }
 
     pub fn send_extrinsic(&self, json_req: String, result_in: ThreadOut<String>) -> WsResult<()> {
-        self.start_rpc_client_thread(json_req, result_in, on_extrinsic_msg_until_ready)
+        self.send_request(json_req, result_in, on_extrinsic_msg_until_ready)
     }
 
     pub fn send_extrinsic_and_wait_until_broadcast(
@@ -105,7 +247,7 @@ impl WsRpcClient {
         json_req: String,
         result_in: ThreadOut<String>,
     ) -> WsResult<()> {
-        self.start_rpc_client_thread(json_req, result_in, on_extrinsic_msg_until_broadcast)
+        self.send_request(json_req, result_in, on_extrinsic_msg_until_broadcast)
     }
 
     pub fn send_extrinsic_and_wait_until_in_block(
@@ -113,7 +255,7 @@ impl WsRpcClient {
         json_req: String,
         result_in: ThreadOut<String>,
     ) -> WsResult<()> {
-        self.start_rpc_client_thread(json_req, result_in, on_extrinsic_msg_until_in_block)
+        self.send_request(json_req, result_in, on_extrinsic_msg_until_in_block)
     }
 
     pub fn send_extrinsic_and_wait_until_finalized(
@@ -121,31 +263,65 @@ impl WsRpcClient {
         json_req: String,
         result_in: ThreadOut<String>,
     ) -> WsResult<()> {
-        self.start_rpc_client_thread(json_req, result_in, on_extrinsic_msg_until_finalized)
+        self.send_request(json_req, result_in, on_extrinsic_msg_until_finalized)
+    }
+
+    pub fn start_subscriber(
+        &self,
+        json_req: String,
+        result_in: ThreadOut<String>,
+    ) -> ApiResult<()> {
+        self.subscribe(&json_req, result_in, None)
     }
 
-    pub fn start_subscriber(&self, json_req: String, result_in: ThreadOut<String>) -> WsResult<()> {
-        self.start_rpc_client_thread(json_req, result_in, on_subscription_msg)
+    /// Subscribes and waits until the node accepted the subscription. A rejection is returned as
+    /// `ApiClientError::Rpc`.
+    fn subscribe(
+        &self,
+        json_req: &str,
+        result_in: ThreadOut<String>,
+        reconnected_in: Option<ThreadOut<Reconnected>>,
+    ) -> ApiResult<()> {
+        let deadline = deadline(self.timeout);
+        let (confirmed_in, confirmed_out) = channel();
+        let connection = self.connection()?;
+        let id = connection.subscribe(
+            json_req,
+            result_in,
+            on_subscription_msg,
+            reconnected_in,
+            Some(confirmed_in),
+        )?;
+
+        let str = wait(&connection, id, &confirmed_out, deadline, None)?;
+        response_result(&serde_json::from_str(&str)?).map(|_| ())
     }
 
-    fn start_rpc_client_thread(
+    fn send_request(
         &self,
         jsonreq: String,
         result_in: ThreadOut<String>,
         on_message_fn: OnMessageFn,
     ) -> WsResult<()> {
-        let url = self.url.clone();
-        let _client =
-            thread::Builder::new()
-                .name("client".to_owned())
-                .spawn(move || -> WsResult<()> {
-                    connect(url, |out| RpcClient {
-                        out,
-                        request: jsonreq.clone(),
-                        result: result_in.clone(),
-                        on_message_fn,
-                    })
-                })?;
-        Ok(())
+        self.connection()?
+            .request(&jsonreq, result_in, on_message_fn)
+            .map(|_| ())
+    }
+
+    fn connection(&self) -> WsResult<Arc<WsConnection>> {
+        let mut connection = self.connection.lock().unwrap();
+        match connection.as_ref() {
+            Some(c) if !c.is_closed() => Ok(c.clone()),
+            _ => {
+                let c = Arc::new(WsConnection::connect(
+                    &self.url,
+                    self.timeout,
+                    self.reconnect.clone(),
+                    &self.handshake,
+                )?);
+                *connection = Some(c.clone());
+                Ok(c)
+            }
+        }
     }
 }
@@ -0,0 +1,851 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! A single long-lived WebSocket connection that multiplexes requests and subscriptions.
+//!
+//! Every request gets a unique JSON-RPC id. Responses are routed back to the caller by that id,
+//! subscription notifications by the subscription id the node returned for the request.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::mem;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::mpsc::{channel, Sender as ThreadOut};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use log::{debug, error, info, warn};
+use openssl::ssl::SslStream;
+use serde_json::Value;
+use sp_runtime::generic::Header;
+use sp_runtime::traits::{BlakeTwo256, Header as _};
+use ws::util::TcpStream;
+use ws::{CloseCode, ErrorKind, Handler, Handshake, Message, Request, Result as WsResult, Sender};
+
+use crate::std::rpc::json_req;
+use crate::std::rpc::ws_client::handshake::{HandshakeConfig, PreparedHandshake};
+use crate::std::rpc::ws_client::OnMessageFn;
+use crate::std::rpc::{response_id, unsubscribe_method, RpcClientError};
+use crate::std::FromHexString;
+use crate::Hash;
+
+/// Sent to a subscriber whenever its subscription was re-issued on a new connection.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reconnected {
+    /// Block of the last storage change set or header received before the connection was lost.
+    /// Messages after this block may have been missed. `None` if nothing was received yet or
+    /// the subscription does not report blocks.
+    pub missed_from: Option<Hash>,
+}
+
+/// How a lost connection is re-established for its active subscriptions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReconnectPolicy {
+    /// Wait before the first attempt. Doubled after every failed attempt.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Give up after this many failed attempts in a row. `None` retries forever.
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            max_attempts: None,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff
+            .checked_mul(2u32.saturating_pow(attempt))
+            .map_or(self.max_backoff, |b| b.min(self.max_backoff))
+    }
+}
+
+/// Where the messages belonging to one request are delivered to.
+struct Route {
+    /// JSON-RPC id of the request.
+    id: u32,
+    result: ThreadOut<String>,
+    on_message_fn: OnMessageFn,
+    /// Method to cancel the subscription with, if the request opens one.
+    unsubscribe_method: Option<&'static str>,
+    /// The subscription request, kept to re-issue it after a reconnect.
+    request: Option<Value>,
+    /// Notified whenever the subscription was re-issued.
+    reconnected: Option<ThreadOut<Reconnected>>,
+    /// Receives the first response to the subscription request, so the subscriber learns whether
+    /// the node accepted it. If the connection dropped before the node answered, that is the
+    /// response to the re-issued request.
+    confirmed: Option<ThreadOut<String>>,
+    last_block: Option<Hash>,
+    /// Whether the request is a batch.
+    batch: bool,
+}
+
+impl Route {
+    /// Returns `true` if the route is completed.
+    fn handle(&self, msg: &str) -> bool {
+        match (self.on_message_fn)(msg, &self.result) {
+            Ok(done) => done,
+            Err(e) => {
+                error!("error handling message: {}", e);
+                true
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Routes {
+    /// Requests waiting for their response, by JSON-RPC id.
+    pending: HashMap<u32, Route>,
+    /// Active subscriptions, by the subscription id returned by the node.
+    subscriptions: HashMap<String, Route>,
+    /// Subscriptions to re-issue once the connection is re-established.
+    resume: Vec<Route>,
+    /// Subscription requests given up before the node answered, with the method to cancel the
+    /// subscription with once it does.
+    cancelled: HashMap<u32, &'static str>,
+}
+
+/// Block a subscription message belongs to: the `block` of a storage change set, or the hash of
+/// the header sent by a head subscription.
+fn message_block(result: &Value) -> Option<Hash> {
+    if let Some(block) = result["block"].as_str() {
+        return Hash::from_hex(block.to_string()).ok();
+    }
+    // the block number is compact encoded, so its type doesn't change the hash
+    serde_json::from_value::<Header<u64, BlakeTwo256>>(result.clone())
+        .ok()
+        .map(|header| header.hash())
+}
+
+impl Routes {
+    /// Routes `msg` to the request it belongs to.
+    ///
+    /// Returns the subscription id and the method to cancel it with, if a subscription was
+    /// completed on the client side.
+    fn dispatch(&mut self, msg: &str, value: &Value) -> Option<(Value, &'static str)> {
+        if let Some(id) = response_id(value).or_else(|| self.rejected_batch(value)) {
+            let mut route = match self.pending.remove(&id) {
+                Some(route) => route,
+                None => {
+                    let method = self.cancelled.remove(&id);
+                    let subscription = &value["result"];
+                    if method.is_none() || subscription.is_null() {
+                        debug!("no pending request for response with id {}", id);
+                        return None;
+                    }
+                    return method.map(|method| (subscription.clone(), method));
+                }
+            };
+            if let Some(confirmed) = route.confirmed.take() {
+                // the subscriber may have given up waiting
+                let _ = confirmed.send(msg.to_string());
+            }
+            let subscription = &value["result"];
+            if !route.handle(msg) && value["error"].is_null() && !subscription.is_null() {
+                self.subscriptions.insert(subscription.to_string(), route);
+            }
+            return None;
+        }
+
+        let subscription = &value["params"]["subscription"];
+        let key = subscription.to_string();
+        let done = match self.subscriptions.get_mut(&key) {
+            Some(route) => {
+                if let Some(block) = message_block(&value["params"]["result"]) {
+                    route.last_block = Some(block);
+                }
+                route.handle(msg)
+            }
+            None => {
+                warn!("received message without a matching request: {}", msg);
+                return None;
+            }
+        };
+        if !done {
+            return None;
+        }
+        self.subscriptions
+            .remove(&key)
+            .and_then(|route| route.unsubscribe_method)
+            .map(|method| (subscription.clone(), method))
+    }
+
+    /// Drops the route of the request `id`, so later messages for it are ignored.
+    ///
+    /// Returns the subscription id and the method to cancel it with, if the request opened a
+    /// subscription on the node.
+    fn cancel(&mut self, id: u32) -> Option<(Value, &'static str)> {
+        if let Some(route) = self.pending.remove(&id) {
+            if let Some(method) = route.unsubscribe_method {
+                // the subscription id is not known yet, it is cancelled once it arrives
+                self.cancelled.insert(id, method);
+            }
+            return None;
+        }
+        let key = self
+            .subscriptions
+            .iter()
+            .find(|(_, route)| route.id == id)
+            .map(|(key, _)| key.clone())?;
+        let method = self.subscriptions.remove(&key)?.unsubscribe_method?;
+        let subscription = serde_json::from_str(&key).unwrap_or(Value::String(key));
+        Some((subscription, method))
+    }
+
+    /// The node answers a batch it rejects as a whole with a single error without id. It is
+    /// attributed to the oldest pending batch.
+    fn rejected_batch(&self, value: &Value) -> Option<u32> {
+        if !value.is_object() || !value["id"].is_null() || value["error"].is_null() {
+            return None;
+        }
+        self.pending
+            .iter()
+            .filter(|(_, route)| route.batch)
+            .map(|(id, _)| *id)
+            .min()
+    }
+
+    /// Drops all routes that can not survive a reconnect and keeps the others for resuming.
+    ///
+    /// Returns `true` if there is anything to resume.
+    fn prepare_resume(&mut self) -> bool {
+        let routes = self.pending.drain().map(|(_, r)| r);
+        let routes = routes.chain(self.subscriptions.drain().map(|(_, r)| r));
+        let resumable: Vec<Route> = routes.filter(|r| r.request.is_some()).collect();
+        // their subscriptions ended with the connection
+        self.cancelled.clear();
+        self.resume.extend(resumable);
+        !self.resume.is_empty()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pending.is_empty() && self.subscriptions.is_empty() && self.resume.is_empty()
+    }
+}
+
+#[derive(Default)]
+struct Shared {
+    routes: Mutex<Routes>,
+    /// Sender of the current connection. `None` while reconnecting.
+    out: Mutex<Option<Sender>>,
+    next_id: AtomicU32,
+    /// Set whenever a connection has been opened.
+    opened: AtomicBool,
+    closed: AtomicBool,
+    /// Set once the owning `WsConnection` is dropped. The connection is then closed as soon as
+    /// the last subscription has ended.
+    detached: AtomicBool,
+}
+
+impl Shared {
+    fn next_id(&self) -> u32 {
+        self.next_ids(1)
+    }
+
+    /// Reserves `count` consecutive ids and returns the first one.
+    fn next_ids(&self, count: u32) -> u32 {
+        self.next_id.fetch_add(count, Ordering::SeqCst)
+    }
+
+    fn send(&self, msg: String) -> WsResult<()> {
+        match self.out.lock().unwrap().as_ref() {
+            Some(out) => out.send(msg),
+            None => Err(ws::Error::new(
+                ErrorKind::Internal,
+                "WebSocket connection lost. Reconnecting",
+            )),
+        }
+    }
+
+    fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        // dropping the routes disconnects all receivers that still wait for messages
+        *self.routes.lock().unwrap() = Routes::default();
+    }
+}
+
+struct ConnectionHandler {
+    out: Sender,
+    shared: Arc<Shared>,
+    opened: Option<ThreadOut<Sender>>,
+    handshake: PreparedHandshake,
+}
+
+impl ConnectionHandler {
+    fn resume_subscriptions(&self) -> WsResult<()> {
+        let mut routes = self.shared.routes.lock().unwrap();
+        for mut route in mem::take(&mut routes.resume) {
+            let id = self.shared.next_id();
+            let request = match route.request.as_mut() {
+                Some(request) => request,
+                None => continue,
+            };
+            set_id(request, id)?;
+            let request = request.to_string();
+            route.id = id;
+
+            if let Some(reconnected) = &route.reconnected {
+                let missed_from = route.last_block;
+                if reconnected.send(Reconnected { missed_from }).is_err() {
+                    debug!("nobody listens for reconnects of {}", request);
+                }
+            }
+            info!("resuming subscription: {}", request);
+            routes.pending.insert(id, route);
+            self.out.send(request)?;
+        }
+        Ok(())
+    }
+}
+
+impl Handler for ConnectionHandler {
+    fn build_request(&mut self, url: &url::Url) -> WsResult<Request> {
+        self.handshake.request(url)
+    }
+
+    fn upgrade_ssl_client(
+        &mut self,
+        sock: TcpStream,
+        url: &url::Url,
+    ) -> WsResult<SslStream<TcpStream>> {
+        self.handshake.upgrade(sock, url)
+    }
+
+    fn on_open(&mut self, _: Handshake) -> WsResult<()> {
+        info!("websocket connection opened");
+        *self.shared.out.lock().unwrap() = Some(self.out.clone());
+        self.shared.opened.store(true, Ordering::SeqCst);
+
+        if let Some(opened) = self.opened.take() {
+            if opened.send(self.out.clone()).is_err() {
+                warn!("connection opened after the caller gave up. closing it");
+                self.out.close(CloseCode::Normal)?;
+            }
+        }
+        self.resume_subscriptions()
+    }
+
+    fn on_message(&mut self, msg: Message) -> WsResult<()> {
+        let msg = msg.as_text()?;
+        let value: Value = match serde_json::from_str(msg) {
+            Ok(v) => v,
+            Err(e) => {
+                error!("could not parse message {}: {}", msg, e);
+                return Ok(());
+            }
+        };
+
+        let mut routes = self.shared.routes.lock().unwrap();
+        if let Some((subscription, method)) = routes.dispatch(msg, &value) {
+            let id = self.shared.next_id();
+            let jsonreq = json_req::unsubscribe_with_id(method, subscription, id);
+            debug!("cancelling subscription: {}", jsonreq);
+            self.out.send(jsonreq.to_string())?;
+        }
+
+        if routes.is_empty() && self.shared.detached.load(Ordering::SeqCst) {
+            self.out.close(CloseCode::Normal)?;
+        }
+        Ok(())
+    }
+
+    fn on_close(&mut self, code: CloseCode, reason: &str) {
+        info!("websocket connection closed: {:?} {}", code, reason);
+        *self.shared.out.lock().unwrap() = None;
+    }
+}
+
+/// A persistent WebSocket connection to a node.
+///
+/// The connection is driven by a background thread and closed when it is dropped and no
+/// subscription is active anymore. With a `ReconnectPolicy`, a lost connection is re-established
+/// as long as there are subscriptions, which are then re-issued. Other pending requests fail.
+pub struct WsConnection {
+    shared: Arc<Shared>,
+}
+
+impl fmt::Debug for WsConnection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WsConnection")
+            .field("closed", &self.is_closed())
+            .finish()
+    }
+}
+
+impl WsConnection {
+    /// Opens a connection to `url` and blocks until the handshake is done or `timeout` passed.
+    pub fn connect(
+        url: &str,
+        timeout: Option<Duration>,
+        reconnect: Option<ReconnectPolicy>,
+        handshake: &HandshakeConfig,
+    ) -> WsResult<Self> {
+        let handshake = handshake.prepare()?;
+        let shared = Arc::new(Shared {
+            next_id: AtomicU32::new(1),
+            ..Default::default()
+        });
+        let (opened_in, opened_out) = channel();
+
+        let url = url.to_string();
+        let handler_shared = shared.clone();
+        thread::Builder::new()
+            .name("ws-client".to_owned())
+            .spawn(move || {
+                let mut opened = Some(opened_in);
+                let mut ever_opened = false;
+                let mut attempt = 0;
+                loop {
+                    if let Err(e) = ws::connect(url.as_str(), |out| ConnectionHandler {
+                        out,
+                        shared: handler_shared.clone(),
+                        opened: opened.take(),
+                        handshake: handshake.clone(),
+                    }) {
+                        error!("websocket connection failed: {}", e);
+                    }
+                    if handler_shared.opened.swap(false, Ordering::SeqCst) {
+                        ever_opened = true;
+                        attempt = 0;
+                    }
+
+                    let policy = match &reconnect {
+                        Some(policy) if ever_opened => policy,
+                        _ => break,
+                    };
+                    if !handler_shared.routes.lock().unwrap().prepare_resume()
+                        || policy.max_attempts.map_or(false, |max| attempt >= max)
+                    {
+                        break;
+                    }
+                    let backoff = policy.backoff(attempt);
+                    warn!("websocket connection lost. reconnecting in {:?}", backoff);
+                    thread::sleep(backoff);
+                    attempt += 1;
+                }
+                handler_shared.close();
+            })?;
+
+        let opened = match timeout {
+            Some(timeout) => opened_out.recv_timeout(timeout).map_err(|e| e.to_string()),
+            None => opened_out.recv().map_err(|e| e.to_string()),
+        };
+        opened.map_err(|e| {
+            ws::Error::new(
+                ErrorKind::Internal,
+                format!("WebSocket connection could not be opened: {}", e),
+            )
+        })?;
+        Ok(Self { shared })
+    }
+
+    /// Sends `jsonreq` with a fresh id. All messages that belong to the request are passed to
+    /// `on_message_fn` until it reports the request as completed.
+    ///
+    /// Returns the id of the request. The requests of a batch get consecutive ids, starting with
+    /// the returned one.
+    pub fn request(
+        &self,
+        jsonreq: &str,
+        result: ThreadOut<String>,
+        on_message_fn: OnMessageFn,
+    ) -> WsResult<u32> {
+        self.send_request(jsonreq, result, on_message_fn, false, None, None)
+    }
+
+    /// Like `request`, but the subscription is re-issued after a reconnect. `reconnected` is
+    /// notified every time this happens. `confirmed` receives the first response of the node to
+    /// the subscription request, later responses to re-issued requests are not forwarded.
+    pub fn subscribe(
+        &self,
+        jsonreq: &str,
+        result: ThreadOut<String>,
+        on_message_fn: OnMessageFn,
+        reconnected: Option<ThreadOut<Reconnected>>,
+        confirmed: Option<ThreadOut<String>>,
+    ) -> WsResult<u32> {
+        self.send_request(jsonreq, result, on_message_fn, true, reconnected, confirmed)
+    }
+
+    fn send_request(
+        &self,
+        jsonreq: &str,
+        result: ThreadOut<String>,
+        on_message_fn: OnMessageFn,
+        resumable: bool,
+        reconnected: Option<ThreadOut<Reconnected>>,
+        confirmed: Option<ThreadOut<String>>,
+    ) -> WsResult<u32> {
+        let mut value: Value =
+            serde_json::from_str(jsonreq).map_err(|e| Box::new(RpcClientError::Serde(e)))?;
+        let (id, unsubscribe_method) = match &mut value {
+            // a batch is routed by the id of its first request
+            Value::Array(batch) => {
+                let id = self.shared.next_ids(batch.len().max(1) as u32);
+                for (i, req) in batch.iter_mut().enumerate() {
+                    set_id(req, id + i as u32)?;
+                }
+                (id, None)
+            }
+            req => {
+                let id = self.shared.next_id();
+                set_id(req, id)?;
+                (id, req["method"].as_str().and_then(unsubscribe_method))
+            }
+        };
+        let batch = value.is_array();
+
+        self.shared.routes.lock().unwrap().pending.insert(
+            id,
+            Route {
+                id,
+                result,
+                on_message_fn,
+                unsubscribe_method,
+                request: if resumable { Some(value.clone()) } else { None },
+                reconnected,
+                confirmed,
+                last_block: None,
+                batch,
+            },
+        );
+
+        info!("sending request: {}", value);
+        self.shared
+            .send(value.to_string())
+            .map(|_| id)
+            .map_err(|e| {
+                self.shared.routes.lock().unwrap().pending.remove(&id);
+                e
+            })
+    }
+
+    /// Stops waiting for the request `id`, e.g. after a timeout. Drops its route and cancels
+    /// the subscription it opened on the node, if any.
+    pub fn cancel(&self, id: u32) {
+        let mut routes = self.shared.routes.lock().unwrap();
+        if let Some((subscription, method)) = routes.cancel(id) {
+            let jsonreq =
+                json_req::unsubscribe_with_id(method, subscription, self.shared.next_id());
+            debug!("cancelling subscription: {}", jsonreq);
+            if let Err(e) = self.shared.send(jsonreq.to_string()) {
+                warn!("could not cancel subscription: {}", e);
+            }
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.shared.closed.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for WsConnection {
+    fn drop(&mut self) {
+        self.shared.detached.store(true, Ordering::SeqCst);
+        if self.shared.routes.lock().unwrap().is_empty() {
+            if let Some(out) = self.shared.out.lock().unwrap().as_ref() {
+                out.close(CloseCode::Normal)
+                    .unwrap_or_else(|_| warn!("Could not close WebSocket normally"));
+            }
+        }
+    }
+}
+
+fn set_id(req: &mut Value, id: u32) -> WsResult<()> {
+    match req.as_object_mut() {
+        Some(obj) => {
+            obj.insert("id".to_string(), Value::String(id.to_string()));
+            Ok(())
+        }
+        None => Err(ws::Error::new(
+            ErrorKind::Protocol,
+            "JSON-RPC request must be an object",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::std::rpc::ws_client::{on_batch_msg, on_get_request_msg, on_subscription_msg};
+    use std::sync::mpsc::Receiver;
+
+    fn route(on_message_fn: OnMessageFn, method: &str) -> (Route, Receiver<String>) {
+        let (result, receiver) = channel();
+        let route = Route {
+            id: 0,
+            result,
+            on_message_fn,
+            unsubscribe_method: unsubscribe_method(method),
+            request: None,
+            reconnected: None,
+            confirmed: None,
+            last_block: None,
+            batch: method.is_empty(),
+        };
+        (route, receiver)
+    }
+
+    fn dispatch(routes: &mut Routes, msg: &str) -> Option<(Value, &'static str)> {
+        routes.dispatch(msg, &serde_json::from_str(msg).unwrap())
+    }
+
+    #[test]
+    fn responses_are_routed_by_id() {
+        let mut routes = Routes::default();
+        let (first, first_out) = route(on_get_request_msg, "state_getStorage");
+        let (second, second_out) = route(on_get_request_msg, "state_getStorage");
+        routes.pending.insert(1, first);
+        routes.pending.insert(2, second);
+
+        let second_response = r#"{"jsonrpc":"2.0","result":"0x02","id":"2"}"#;
+        let first_response = r#"{"jsonrpc":"2.0","result":"0x01","id":"1"}"#;
+        dispatch(&mut routes, second_response);
+        dispatch(&mut routes, first_response);
+
+        assert_eq!(first_out.recv().unwrap(), first_response);
+        assert_eq!(second_out.recv().unwrap(), second_response);
+        assert!(routes.is_empty());
+    }
+
+    #[test]
+    fn batch_responses_are_routed_by_first_id() {
+        let mut routes = Routes::default();
+        let (batch, batch_out) = route(on_batch_msg, "");
+        routes.pending.insert(3, batch);
+
+        let msg = r#"[{"jsonrpc":"2.0","result":"0x05","id":"5"},{"jsonrpc":"2.0","result":null,"id":"3"}]"#;
+        dispatch(&mut routes, msg);
+
+        assert_eq!(batch_out.recv().unwrap(), msg);
+        assert!(routes.is_empty());
+    }
+
+    #[test]
+    fn rejected_batches_get_the_error() {
+        let mut routes = Routes::default();
+        let (get, _get_out) = route(on_get_request_msg, "state_getStorage");
+        let (batch, batch_out) = route(on_batch_msg, "");
+        routes.pending.insert(2, get);
+        routes.pending.insert(3, batch);
+
+        let msg =
+            r#"{"jsonrpc":"2.0","error":{"code":-32600,"message":"Invalid request"},"id":null}"#;
+        dispatch(&mut routes, msg);
+
+        assert_eq!(batch_out.recv().unwrap(), msg);
+        assert!(routes.pending.contains_key(&2));
+        assert!(!routes.pending.contains_key(&3));
+    }
+
+    #[test]
+    fn subscriptions_are_routed_by_subscription_id() {
+        let mut routes = Routes::default();
+        let (sub, sub_out) = route(on_subscription_msg, "chain_subscribeFinalizedHeads");
+        routes.pending.insert(1, sub);
+
+        dispatch(&mut routes, r#"{"jsonrpc":"2.0","result":42,"id":"1"}"#);
+        assert!(routes.pending.is_empty());
+        assert!(routes.subscriptions.contains_key("42"));
+
+        let msg = r#"{"jsonrpc":"2.0","method":"chain_finalizedHead","params":{"result":{"number":"0x1"},"subscription":42}}"#;
+        assert_eq!(dispatch(&mut routes, msg), None);
+        assert_eq!(sub_out.recv().unwrap(), r#"{"number":"0x1"}"#);
+
+        drop(sub_out);
+        assert_eq!(
+            dispatch(&mut routes, msg),
+            Some((Value::from(42), "chain_unsubscribeFinalizedHeads"))
+        );
+        assert!(routes.is_empty());
+    }
+
+    #[test]
+    fn rejected_subscriptions_are_reported_to_the_subscriber() {
+        let mut routes = Routes::default();
+        let (mut sub, sub_out) = route(on_subscription_msg, "state_subscribeStorage");
+        let (confirmed, confirmed_out) = channel();
+        sub.confirmed = Some(confirmed);
+        routes.pending.insert(1, sub);
+
+        let msg =
+            r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Invalid params"},"id":"1"}"#;
+        assert_eq!(dispatch(&mut routes, msg), None);
+        assert_eq!(confirmed_out.recv().unwrap(), msg);
+        assert!(routes.is_empty());
+        assert!(sub_out.recv().is_err());
+    }
+
+    #[test]
+    fn subscriptions_remember_their_last_block() {
+        let mut routes = Routes::default();
+        let (sub, _sub_out) = route(on_subscription_msg, "state_subscribeStorage");
+        routes.subscriptions.insert("7".to_string(), sub);
+
+        let msg = r#"{"jsonrpc":"2.0","method":"state_storage","params":{"result":{"block":"0x934385b11c483498e2b5bca64c2e8ef76ad6c74d3372a05595d3a50caf758d52","changes":[]},"subscription":7}}"#;
+        dispatch(&mut routes, msg);
+
+        assert_eq!(
+            routes.subscriptions["7"].last_block,
+            Some(
+                Hash::from_hex(
+                    "0x934385b11c483498e2b5bca64c2e8ef76ad6c74d3372a05595d3a50caf758d52"
+                        .to_string()
+                )
+                .unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn head_subscriptions_remember_the_hash_of_their_last_header() {
+        let mut routes = Routes::default();
+        let (sub, _sub_out) = route(on_subscription_msg, "chain_subscribeFinalizedHeads");
+        routes.subscriptions.insert("7".to_string(), sub);
+
+        let header = Header::<u32, BlakeTwo256>::new(
+            12,
+            Hash::repeat_byte(1),
+            Hash::repeat_byte(2),
+            Hash::repeat_byte(3),
+            Default::default(),
+        );
+        let msg = serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "chain_finalizedHead",
+            "params": { "result": header, "subscription": 7 },
+        })
+        .to_string();
+        dispatch(&mut routes, &msg);
+
+        assert_eq!(routes.subscriptions["7"].last_block, Some(header.hash()));
+    }
+
+    #[test]
+    fn cancelled_requests_are_dropped() {
+        let mut routes = Routes::default();
+        let (get, get_out) = route(on_get_request_msg, "state_getStorage");
+        routes.pending.insert(1, get);
+
+        assert_eq!(routes.cancel(1), None);
+        assert!(routes.is_empty());
+        dispatch(&mut routes, r#"{"jsonrpc":"2.0","result":"0x01","id":"1"}"#);
+        assert!(get_out.recv().is_err());
+    }
+
+    #[test]
+    fn cancelled_watches_are_unwatched() {
+        let mut routes = Routes::default();
+        let (mut watch, _watch_out) = route(on_subscription_msg, "author_submitAndWatchExtrinsic");
+        watch.id = 1;
+        routes.pending.insert(1, watch);
+        dispatch(&mut routes, r#"{"jsonrpc":"2.0","result":42,"id":"1"}"#);
+
+        assert_eq!(
+            routes.cancel(1),
+            Some((Value::from(42), "author_unwatchExtrinsic"))
+        );
+        assert!(routes.is_empty());
+    }
+
+    #[test]
+    fn watches_cancelled_before_the_answer_are_unwatched_on_arrival() {
+        let mut routes = Routes::default();
+        let (watch, _watch_out) = route(on_subscription_msg, "author_submitAndWatchExtrinsic");
+        routes.pending.insert(1, watch);
+
+        assert_eq!(routes.cancel(1), None);
+        assert!(routes.pending.is_empty());
+        assert_eq!(
+            dispatch(&mut routes, r#"{"jsonrpc":"2.0","result":"abc","id":"1"}"#),
+            Some((Value::from("abc"), "author_unwatchExtrinsic"))
+        );
+        assert!(routes.cancelled.is_empty());
+        assert!(routes.is_empty());
+    }
+
+    #[test]
+    fn only_subscriptions_are_resumed() {
+        let mut routes = Routes::default();
+        let (get, _get_out) = route(on_get_request_msg, "state_getStorage");
+        let (mut sub, _sub_out) = route(on_subscription_msg, "state_subscribeStorage");
+        sub.request = Some(serde_json::json!({"method": "state_subscribeStorage"}));
+        routes.pending.insert(1, get);
+        routes.subscriptions.insert("7".to_string(), sub);
+
+        assert!(routes.prepare_resume());
+        assert!(routes.pending.is_empty());
+        assert!(routes.subscriptions.is_empty());
+        assert_eq!(routes.resume.len(), 1);
+    }
+
+    #[test]
+    fn resumed_subscriptions_are_confirmed_once() {
+        let mut routes = Routes::default();
+        let (confirmed_out, unconfirmed_out) = {
+            let mut subscribe = |id| {
+                let (mut sub, _sub_out) = route(on_subscription_msg, "state_subscribeStorage");
+                let (confirmed, confirmed_out) = channel();
+                sub.id = id;
+                sub.request = Some(serde_json::json!({"method": "state_subscribeStorage"}));
+                sub.confirmed = Some(confirmed);
+                routes.pending.insert(id, sub);
+                confirmed_out
+            };
+            (subscribe(1), subscribe(2))
+        };
+        let accepted = r#"{"jsonrpc":"2.0","result":"7","id":"1"}"#;
+        dispatch(&mut routes, accepted);
+        assert_eq!(confirmed_out.recv().unwrap(), accepted);
+
+        // the connection drops before the node answered the second subscription
+        assert!(routes.prepare_resume());
+        for mut route in mem::take(&mut routes.resume) {
+            route.id += 10;
+            routes.pending.insert(route.id, route);
+        }
+        dispatch(&mut routes, r#"{"jsonrpc":"2.0","result":"8","id":"11"}"#);
+        let resumed = r#"{"jsonrpc":"2.0","result":"9","id":"12"}"#;
+        dispatch(&mut routes, resumed);
+
+        assert!(confirmed_out.try_recv().is_err());
+        assert_eq!(unconfirmed_out.recv().unwrap(), resumed);
+        assert_eq!(routes.subscriptions.len(), 2);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        let policy = ReconnectPolicy {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(5),
+            max_attempts: None,
+        };
+        assert_eq!(policy.backoff(0), Duration::from_secs(1));
+        assert_eq!(policy.backoff(2), Duration::from_secs(4));
+        assert_eq!(policy.backoff(3), Duration::from_secs(5));
+        assert_eq!(policy.backoff(40), Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,177 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! TLS and HTTP settings of the WebSocket handshake.
+
+use std::fmt;
+use std::path::PathBuf;
+
+use log::warn;
+use openssl::ssl::{SslConnector, SslFiletype, SslMethod, SslStream, SslVerifyMode};
+use ws::util::TcpStream;
+use ws::{ErrorKind, Request, Result as WsResult};
+
+/// TLS settings for `wss://` urls. By default, the system's root certificates are trusted.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TlsConfig {
+    /// PEM file with additional CA certificates to trust.
+    pub ca_bundle: Option<PathBuf>,
+    /// PEM files of the certificate chain and private key to authenticate the client with.
+    pub client_certificate: Option<(PathBuf, PathBuf)>,
+    /// Accepts any server certificate, including self-signed ones. Only meant for local devnets.
+    pub accept_invalid_certificates: bool,
+}
+
+impl TlsConfig {
+    pub fn set_ca_bundle<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.ca_bundle = Some(path.into());
+        self
+    }
+
+    pub fn set_client_certificate<P: Into<PathBuf>>(mut self, certificate: P, key: P) -> Self {
+        self.client_certificate = Some((certificate.into(), key.into()));
+        self
+    }
+
+    pub fn set_accept_invalid_certificates(mut self, accept: bool) -> Self {
+        self.accept_invalid_certificates = accept;
+        self
+    }
+
+    fn connector(&self) -> WsResult<SslConnector> {
+        let mut builder = SslConnector::builder(SslMethod::tls()).map_err(tls_error)?;
+        if let Some(ca_bundle) = &self.ca_bundle {
+            builder.set_ca_file(ca_bundle).map_err(tls_error)?;
+        }
+        if let Some((certificate, key)) = &self.client_certificate {
+            builder
+                .set_certificate_chain_file(certificate)
+                .map_err(tls_error)?;
+            builder
+                .set_private_key_file(key, SslFiletype::PEM)
+                .map_err(tls_error)?;
+            builder.check_private_key().map_err(tls_error)?;
+        }
+        if self.accept_invalid_certificates {
+            warn!("TLS certificates are not verified. Do not use this outside of local devnets");
+            builder.set_verify(SslVerifyMode::NONE);
+        }
+        Ok(builder.build())
+    }
+}
+
+/// Everything a `WsConnection` needs for the handshake, besides the url.
+#[derive(Clone, Default, PartialEq)]
+pub struct HandshakeConfig {
+    pub tls: TlsConfig,
+    /// Additional HTTP headers of the upgrade request, e.g. `Authorization`.
+    pub headers: Vec<(String, String)>,
+}
+
+/// Header values are left out, since they usually contain credentials.
+impl fmt::Debug for HandshakeConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let headers: Vec<&str> = self.headers.iter().map(|(name, _)| name.as_str()).collect();
+        f.debug_struct("HandshakeConfig")
+            .field("tls", &self.tls)
+            .field("headers", &headers)
+            .finish()
+    }
+}
+
+impl HandshakeConfig {
+    /// Prepares the handshake. Fails if the certificate files can not be loaded.
+    pub(crate) fn prepare(&self) -> WsResult<PreparedHandshake> {
+        Ok(PreparedHandshake {
+            connector: self.tls.connector()?,
+            accept_invalid_certificates: self.tls.accept_invalid_certificates,
+            headers: self.headers.clone(),
+        })
+    }
+}
+
+/// A `HandshakeConfig` with the certificates loaded, shared by all reconnects.
+#[derive(Clone)]
+pub(crate) struct PreparedHandshake {
+    connector: SslConnector,
+    accept_invalid_certificates: bool,
+    headers: Vec<(String, String)>,
+}
+
+impl PreparedHandshake {
+    pub(crate) fn request(&self, url: &url::Url) -> WsResult<Request> {
+        let mut request = Request::from_url(url)?;
+        request.headers_mut().extend(
+            self.headers
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone().into_bytes())),
+        );
+        Ok(request)
+    }
+
+    pub(crate) fn upgrade(
+        &self,
+        sock: TcpStream,
+        url: &url::Url,
+    ) -> WsResult<SslStream<TcpStream>> {
+        let host = url.host_str().ok_or_else(|| {
+            ws::Error::new(
+                ErrorKind::Protocol,
+                format!("Unable to parse host from {}. Needed for TLS.", url),
+            )
+        })?;
+        let mut config = self.connector.configure().map_err(tls_error)?;
+        config.set_verify_hostname(!self.accept_invalid_certificates);
+        config.connect(host, sock).map_err(|e| {
+            ws::Error::new(ErrorKind::Internal, format!("TLS handshake failed: {}", e))
+        })
+    }
+}
+
+fn tls_error(e: openssl::error::ErrorStack) -> ws::Error {
+    ws::Error::new(ErrorKind::Internal, format!("TLS setup failed: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headers_are_added_to_the_upgrade_request() {
+        let handshake = HandshakeConfig {
+            headers: vec![("Authorization".to_string(), "Bearer secret".to_string())],
+            ..Default::default()
+        };
+        let url = url::Url::parse("wss://rpc.example.com/ws").unwrap();
+        let request = handshake.prepare().unwrap().request(&url).unwrap();
+
+        assert_eq!(
+            request.header("authorization"),
+            Some(&b"Bearer secret".to_vec())
+        );
+        assert!(!format!("{:?}", handshake).contains("secret"));
+    }
+
+    #[test]
+    fn missing_certificate_files_fail_early() {
+        let handshake = HandshakeConfig {
+            tls: TlsConfig::default().set_ca_bundle("/does/not/exist.pem"),
+            ..Default::default()
+        };
+        assert!(handshake.prepare().is_err());
+    }
+}
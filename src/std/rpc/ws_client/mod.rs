@@ -16,51 +16,54 @@
 */
 use std::convert::TryFrom;
 use std::sync::mpsc::{Receiver, SendError, Sender as ThreadOut};
+use std::time::Duration;
 
 use codec::Decode;
 use log::{debug, error, info, warn};
 use sp_core::Pair;
 use sp_runtime::MultiSignature;
-use ws::{CloseCode, Error, Handler, Handshake, Message, Result as WsResult, Sender};
 
+use crate::std::rpc::cancel::{deadline, recv, CancelToken};
 use crate::std::rpc::RpcClientError;
 use crate::std::{json_req, FromHexString, RpcClient as RpcClientTrait, XtStatus};
 use crate::std::{Api, ApiResult};
-use crate::{utils, ApiClientError};
+use crate::{utils, ApiClientError, Hash};
 
 pub use client::WsRpcClient;
+pub use connection::{ReconnectPolicy, Reconnected, WsConnection};
 pub use events::EventsError;
 pub use events::{EventsDecoder, RawEvent, RuntimeEvent};
+pub use handshake::{HandshakeConfig, TlsConfig};
 
 pub mod client;
+pub mod connection;
 pub mod events;
+pub mod handshake;
 
-pub type OnMessageFn = fn(msg: Message, out: Sender, result: ThreadOut<String>) -> WsResult<()>;
+/// Handles a message that belongs to a request sent over a [`WsConnection`].
+///
+/// Returns `true` once the request is completed and no further messages are expected.
+pub type OnMessageFn = fn(msg: &str, result: &ThreadOut<String>) -> RpcResult<bool>;
 
 type RpcResult<T> = Result<T, RpcClientError>;
 
-pub struct RpcClient {
-    pub out: Sender,
-    pub request: String,
-    pub result: ThreadOut<String>,
-    pub on_message_fn: OnMessageFn,
-}
-
-impl Handler for RpcClient {
-    fn on_open(&mut self, _: Handshake) -> WsResult<()> {
-        info!("sending request: {}", self.request);
-        self.out.send(self.request.clone())?;
-        Ok(())
-    }
-
-    fn on_message(&mut self, msg: Message) -> WsResult<()> {
-        (self.on_message_fn)(msg, self.out.clone(), self.result.clone())
-    }
-}
-
 pub trait Subscriber {
-    fn start_subscriber(&self, json_req: String, result_in: ThreadOut<String>)
-        -> Result<(), Error>;
+    /// Starts a subscription that sends its messages to `result_in`. Fails with
+    /// `ApiClientError::Rpc` if the node rejects the subscription.
+    fn start_subscriber(&self, json_req: String, result_in: ThreadOut<String>) -> ApiResult<()>;
+
+    /// Like `start_subscriber`, but the subscription survives reconnects of the client.
+    /// `reconnected_in` is notified whenever messages may have been missed.
+    ///
+    /// Clients that do not reconnect never send such a notification.
+    fn start_resumable_subscriber(
+        &self,
+        json_req: String,
+        result_in: ThreadOut<String>,
+        _reconnected_in: ThreadOut<Reconnected>,
+    ) -> ApiResult<()> {
+        self.start_subscriber(json_req, result_in)
+    }
 }
 
 impl<P> Api<P, WsRpcClient> {
@@ -68,6 +71,33 @@ impl<P> Api<P, WsRpcClient> {
         let client = WsRpcClient::new(url);
         Self::new(client)
     }
+
+    pub fn get_request_with_timeout(
+        &self,
+        jsonreq: serde_json::Value,
+        timeout: Option<Duration>,
+    ) -> ApiResult<Option<String>> {
+        let str = self.client.get_request_with_timeout(jsonreq, timeout)?;
+
+        match &str[..] {
+            "null" => Ok(None),
+            _ => Ok(Some(str)),
+        }
+    }
+
+    /// Like `send_extrinsic`, but gives up once `timeout` has passed or `cancel` is cancelled.
+    pub fn send_extrinsic_with_timeout(
+        &self,
+        xthex_prefixed: String,
+        exit_on: XtStatus,
+        timeout: Option<Duration>,
+        cancel: Option<&CancelToken>,
+    ) -> ApiResult<Option<Hash>> {
+        debug!("sending extrinsic: {:?}", xthex_prefixed);
+        self.check_dry_run(&xthex_prefixed)?;
+        self.client
+            .send_extrinsic_with_timeout(xthex_prefixed, exit_on, timeout, cancel)
+    }
 }
 
 impl<P, Client> Api<P, Client>
@@ -77,20 +107,43 @@ where
     Client: RpcClientTrait + Subscriber,
 {
     pub fn subscribe_events(&self, sender: ThreadOut<String>) -> ApiResult<()> {
+        debug!("subscribing to events");
+        let key = utils::storage_key("System", "Events");
+        let jsonreq = json_req::state_subscribe_storage(vec![key]).to_string();
+        self.client.start_subscriber(jsonreq, sender)
+    }
+
+    /// Like `subscribe_events`, but keeps the subscription across reconnects. `reconnected` is
+    /// notified whenever events may have been missed.
+    pub fn subscribe_events_resumable(
+        &self,
+        sender: ThreadOut<String>,
+        reconnected: ThreadOut<Reconnected>,
+    ) -> ApiResult<()> {
         debug!("subscribing to events");
         let key = utils::storage_key("System", "Events");
         let jsonreq = json_req::state_subscribe_storage(vec![key]).to_string();
         self.client
-            .start_subscriber(jsonreq, sender)
-            .map_err(|e| e.into())
+            .start_resumable_subscriber(jsonreq, sender, reconnected)
     }
 
     pub fn subscribe_finalized_heads(&self, sender: ThreadOut<String>) -> ApiResult<()> {
+        debug!("subscribing to finalized heads");
+        let jsonreq = json_req::chain_subscribe_finalized_heads().to_string();
+        self.client.start_subscriber(jsonreq, sender)
+    }
+
+    /// Like `subscribe_finalized_heads`, but keeps the subscription across reconnects.
+    /// `reconnected` is notified whenever heads may have been missed.
+    pub fn subscribe_finalized_heads_resumable(
+        &self,
+        sender: ThreadOut<String>,
+        reconnected: ThreadOut<Reconnected>,
+    ) -> ApiResult<()> {
         debug!("subscribing to finalized heads");
         let jsonreq = json_req::chain_subscribe_finalized_heads().to_string();
         self.client
-            .start_subscriber(jsonreq, sender)
-            .map_err(|e| e.into())
+            .start_resumable_subscriber(jsonreq, sender, reconnected)
     }
 
     pub fn wait_for_event<E: Decode>(
@@ -100,7 +153,21 @@ where
         decoder: Option<EventsDecoder>,
         receiver: &Receiver<String>,
     ) -> ApiResult<E> {
-        let raw = self.wait_for_raw_event(module, variant, decoder, receiver)?;
+        self.wait_for_event_with_timeout(module, variant, decoder, receiver, None, None)
+    }
+
+    /// Like `wait_for_event`, but gives up once `timeout` has passed or `cancel` is cancelled.
+    pub fn wait_for_event_with_timeout<E: Decode>(
+        &self,
+        module: &str,
+        variant: &str,
+        decoder: Option<EventsDecoder>,
+        receiver: &Receiver<String>,
+        timeout: Option<Duration>,
+        cancel: Option<&CancelToken>,
+    ) -> ApiResult<E> {
+        let raw = self
+            .wait_for_raw_event_with_timeout(module, variant, decoder, receiver, timeout, cancel)?;
         E::decode(&mut &raw.data[..]).map_err(|e| e.into())
     }
 
@@ -111,59 +178,84 @@ where
         decoder: Option<EventsDecoder>,
         receiver: &Receiver<String>,
     ) -> ApiResult<RawEvent> {
+        self.wait_for_raw_event_with_timeout(module, variant, decoder, receiver, None, None)
+    }
+
+    /// Like `wait_for_raw_event`, but gives up once `timeout` has passed or `cancel` is
+    /// cancelled.
+    pub fn wait_for_raw_event_with_timeout(
+        &self,
+        module: &str,
+        variant: &str,
+        decoder: Option<EventsDecoder>,
+        receiver: &Receiver<String>,
+        timeout: Option<Duration>,
+        cancel: Option<&CancelToken>,
+    ) -> ApiResult<RawEvent> {
+        let deadline = deadline(timeout);
         let event_decoder = match decoder {
             Some(d) => d,
             None => EventsDecoder::try_from(self.metadata.clone())?,
         };
 
         loop {
-            let event_str = receiver.recv()?;
-            let _events = event_decoder.decode_events(&mut Vec::from_hex(event_str)?.as_slice());
-            info!("wait for raw event");
-            match _events {
-                Ok(raw_events) => {
-                    for (phase, event) in raw_events.into_iter() {
-                        info!("Decoded Event: {:?}, {:?}", phase, event);
-                        match event {
-                            RuntimeEvent::Raw(raw)
-                                if raw.module == module && raw.variant == variant =>
-                            {
-                                return Ok(raw);
-                            }
-                            _ => debug!("ignoring unsupported module event: {:?}", event),
-                        }
+            let event_str = recv(receiver, deadline, cancel)?;
+            if let Some(raw) = find_event(&event_decoder, module, variant, event_str)? {
+                return Ok(raw);
+            }
+        }
+    }
+}
+
+/// Looks for the event `module::variant` in the hex encoded events of a block. Fails if an
+/// extrinsic of the block failed with a module error.
+pub(crate) fn find_event(
+    event_decoder: &EventsDecoder,
+    module: &str,
+    variant: &str,
+    event_str: String,
+) -> ApiResult<Option<RawEvent>> {
+    let _events = event_decoder.decode_events(&mut Vec::from_hex(event_str)?.as_slice());
+    info!("wait for raw event");
+    match _events {
+        Ok(raw_events) => {
+            for (phase, event) in raw_events.into_iter() {
+                info!("Decoded Event: {:?}, {:?}", phase, event);
+                match event {
+                    RuntimeEvent::Raw(raw) if raw.module == module && raw.variant == variant => {
+                        return Ok(Some(raw));
                     }
+                    _ => debug!("ignoring unsupported module event: {:?}", event),
                 }
-                Err(error) => match error {
-                    EventsError::ModuleError(ref msg) => {
-                        error!("Extrinsic Failed: {}", msg);
-                        return Err(ApiClientError::Events(error));
-                    }
-                    _ => error!("couldn't decode event record list"),
-                },
             }
         }
+        Err(error) => match error {
+            EventsError::ModuleError(ref msg) => {
+                error!("Extrinsic Failed: {}", msg);
+                return Err(ApiClientError::Events(error));
+            }
+            _ => error!("couldn't decode event record list"),
+        },
     }
+    Ok(None)
 }
 
-pub fn on_get_request_msg(msg: Message, out: Sender, result: ThreadOut<String>) -> WsResult<()> {
-    out.close(CloseCode::Normal)
-        .unwrap_or_else(|_| warn!("Could not close Websocket normally"));
-
+pub fn on_get_request_msg(msg: &str, result: &ThreadOut<String>) -> RpcResult<bool> {
     info!("Got get_request_msg {}", msg);
-    let result_str = serde_json::from_str(msg.as_text()?)
-        .map(|v: serde_json::Value| v["result"].to_string())
-        .map_err(|e| Box::new(RpcClientError::Serde(e)))?;
+    // the whole response is forwarded, so the caller can tell a `null` result from an error
+    result.send(msg.to_string())?;
+    Ok(true)
+}
 
-    result
-        .send(result_str)
-        .map_err(|e| Box::new(RpcClientError::Send(e)).into())
+pub fn on_batch_msg(msg: &str, result: &ThreadOut<String>) -> RpcResult<bool> {
+    info!("Got batch_msg {}", msg);
+    result.send(msg.to_string())?;
+    Ok(true)
 }
 
-pub fn on_subscription_msg(msg: Message, out: Sender, result: ThreadOut<String>) -> WsResult<()> {
+pub fn on_subscription_msg(msg: &str, result: &ThreadOut<String>) -> RpcResult<bool> {
     info!("got on_subscription_msg {}", msg);
-    let value: serde_json::Value =
-        serde_json::from_str(msg.as_text()?).map_err(|e| Box::new(RpcClientError::Serde(e)))?;
+    let value: serde_json::Value = serde_json::from_str(msg)?;
 
     match value["id"].as_str() {
         Some(_idstr) => {}
@@ -177,118 +269,106 @@ pub fn on_subscription_msg(msg: Message, out: Sender, result: ThreadOut<String>)
                     match changes[0][1].as_str() {
                         Some(change_set) => {
                             if let Err(SendError(e)) = result.send(change_set.to_owned()) {
-                                debug!("SendError: {}. will close ws", e);
-                                out.close(CloseCode::Normal)?;
+                                debug!("SendError: {}. will unsubscribe", e);
+                                return Ok(true);
                             }
                         }
                         None => println!("No events happened"),
                     };
                 }
                 Some("chain_finalizedHead") => {
-                    let head = serde_json::to_string(&value["params"]["result"])
-                        .map_err(|e| Box::new(RpcClientError::Serde(e)))?;
+                    let head = serde_json::to_string(&value["params"]["result"])?;
 
                     if let Err(e) = result.send(head) {
-                        debug!("SendError: {}. will close ws", e);
-                        out.close(CloseCode::Normal)?;
+                        debug!("SendError: {}. will unsubscribe", e);
+                        return Ok(true);
                     }
                 }
                 _ => error!("unsupported method"),
             }
         }
     };
-    Ok(())
+    Ok(false)
+}
+
+/// Forwards every message of an extrinsic watch, until the node ends the watch.
+pub fn on_extrinsic_msg(msg: &str, result: &ThreadOut<String>) -> RpcResult<bool> {
+    debug!("got msg {}", msg);
+    result.send(msg.to_string())?;
+    Ok(matches!(
+        parse_status(msg),
+        Ok((XtStatus::Finalized, _)) | Ok((XtStatus::Future, _)) | Err(_)
+    ))
 }
 
-pub fn on_extrinsic_msg_until_finalized(
-    msg: Message,
-    out: Sender,
-    result: ThreadOut<String>,
-) -> WsResult<()> {
-    let retstr = msg.as_text().unwrap();
-    debug!("got msg {}", retstr);
-    match parse_status(retstr) {
-        Ok((XtStatus::Finalized, val)) => end_process(out, result, val),
+pub fn on_extrinsic_msg_until_finalized(msg: &str, result: &ThreadOut<String>) -> RpcResult<bool> {
+    debug!("got msg {}", msg);
+    match parse_status(msg) {
+        Ok((XtStatus::Finalized, val)) => end_process(result, val),
         Ok((XtStatus::Future, _)) => {
             warn!("extrinsic has 'future' status. aborting");
-            end_process(out, result, None)
+            end_process(result, None)
         }
         Err(e) => {
-            end_process(out, result, None)?;
-            Err(Box::new(e).into())
+            // forward the response, so the caller gets the error of the node
+            end_process(result, Some(msg.to_string()))?;
+            Err(e)
         }
-        _ => Ok(()),
+        _ => Ok(false),
     }
 }
 
-pub fn on_extrinsic_msg_until_in_block(
-    msg: Message,
-    out: Sender,
-    result: ThreadOut<String>,
-) -> WsResult<()> {
-    let retstr = msg.as_text().unwrap();
-    debug!("got msg {}", retstr);
-    match parse_status(retstr) {
-        Ok((XtStatus::Finalized, val)) => end_process(out, result, val),
-        Ok((XtStatus::InBlock, val)) => end_process(out, result, val),
-        Ok((XtStatus::Future, _)) => end_process(out, result, None),
+pub fn on_extrinsic_msg_until_in_block(msg: &str, result: &ThreadOut<String>) -> RpcResult<bool> {
+    debug!("got msg {}", msg);
+    match parse_status(msg) {
+        Ok((XtStatus::Finalized, val)) => end_process(result, val),
+        Ok((XtStatus::InBlock, val)) => end_process(result, val),
+        Ok((XtStatus::Future, _)) => end_process(result, None),
         Err(e) => {
-            end_process(out, result, None)?;
-            Err(Box::new(e).into())
+            // forward the response, so the caller gets the error of the node
+            end_process(result, Some(msg.to_string()))?;
+            Err(e)
         }
-        _ => Ok(()),
+        _ => Ok(false),
     }
 }
 
-pub fn on_extrinsic_msg_until_broadcast(
-    msg: Message,
-    out: Sender,
-    result: ThreadOut<String>,
-) -> WsResult<()> {
-    let retstr = msg.as_text().unwrap();
-    debug!("got msg {}", retstr);
-    match parse_status(retstr) {
-        Ok((XtStatus::Finalized, val)) => end_process(out, result, val),
-        Ok((XtStatus::Broadcast, _)) => end_process(out, result, None),
-        Ok((XtStatus::Future, _)) => end_process(out, result, None),
+pub fn on_extrinsic_msg_until_broadcast(msg: &str, result: &ThreadOut<String>) -> RpcResult<bool> {
+    debug!("got msg {}", msg);
+    match parse_status(msg) {
+        Ok((XtStatus::Finalized, val)) => end_process(result, val),
+        Ok((XtStatus::Broadcast, _)) => end_process(result, None),
+        Ok((XtStatus::Future, _)) => end_process(result, None),
         Err(e) => {
-            end_process(out, result, None)?;
-            Err(Box::new(e).into())
+            // forward the response, so the caller gets the error of the node
+            end_process(result, Some(msg.to_string()))?;
+            Err(e)
         }
-        _ => Ok(()),
+        _ => Ok(false),
     }
 }
 
-pub fn on_extrinsic_msg_until_ready(
-    msg: Message,
-    out: Sender,
-    result: ThreadOut<String>,
-) -> WsResult<()> {
-    let retstr = msg.as_text().unwrap();
-    debug!("got msg {}", retstr);
-    match parse_status(retstr) {
-        Ok((XtStatus::Finalized, val)) => end_process(out, result, val),
-        Ok((XtStatus::Ready, _)) => end_process(out, result, None),
-        Ok((XtStatus::Future, _)) => end_process(out, result, None),
+pub fn on_extrinsic_msg_until_ready(msg: &str, result: &ThreadOut<String>) -> RpcResult<bool> {
+    debug!("got msg {}", msg);
+    match parse_status(msg) {
+        Ok((XtStatus::Finalized, val)) => end_process(result, val),
+        Ok((XtStatus::Ready, _)) => end_process(result, None),
+        Ok((XtStatus::Future, _)) => end_process(result, None),
         Err(e) => {
-            end_process(out, result, None)?;
-            Err(Box::new(e).into())
+            // forward the response, so the caller gets the error of the node
+            end_process(result, Some(msg.to_string()))?;
+            Err(e)
         }
-        _ => Ok(()),
+        _ => Ok(false),
     }
 }
 
-fn end_process(out: Sender, result: ThreadOut<String>, value: Option<String>) -> WsResult<()> {
+fn end_process(result: &ThreadOut<String>, value: Option<String>) -> RpcResult<bool> {
     // return result to calling thread
     debug!("Thread end result :{:?} value:{:?}", result, value);
     let val = value.unwrap_or_else(|| "".to_string());
-
-    out.close(CloseCode::Normal)
-        .unwrap_or_else(|_| warn!("Could not close WebSocket normally"));
-
-    result
-        .send(val)
-        .map_err(|e| Box::new(RpcClientError::Send(e)).into())
+    result.send(val)?;
+    Ok(true)
 }
 
 fn parse_status(msg: &str) -> RpcResult<(XtStatus, Option<String>)> {
@@ -421,4 +501,20 @@ mod tests {
             "extrinsic error code 1002: Verification Error: Execution(Wasmi(Trap(Trap { kind: Unreachable }))): RuntimeApi(\"Execution(Wasmi(Trap(Trap { kind: Unreachable })))\")".to_string()
         );
     }
+
+    #[test]
+    fn extrinsic_watches_forward_every_message_until_the_watch_ends() {
+        let (result_in, result_out) = std::sync::mpsc::channel();
+        let subscribed = r#"{"jsonrpc":"2.0","result":7185,"id":"3"}"#;
+        let ready = r#"{"jsonrpc":"2.0","method":"author_extrinsicUpdate","params":{"result":"ready","subscription":7185}}"#;
+        let finalized = r#"{"jsonrpc":"2.0","method":"author_extrinsicUpdate","params":{"result":{"finalized":"0x934385b11c483498e2b5bca64c2e8ef76ad6c74d3372a05595d3a50caf758d52"},"subscription":7185}}"#;
+
+        assert!(!on_extrinsic_msg(subscribed, &result_in).unwrap());
+        assert!(!on_extrinsic_msg(ready, &result_in).unwrap());
+        assert!(on_extrinsic_msg(finalized, &result_in).unwrap());
+        assert_eq!(
+            result_out.try_iter().collect::<Vec<_>>(),
+            vec![subscribed, ready, finalized]
+        );
+    }
 }
@@ -0,0 +1,227 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! Calls into the runtime APIs of the node with `state_call`.
+
+use codec::{Decode, Encode};
+use log::debug;
+use sp_core::Bytes;
+use sp_runtime::transaction_validity::{TransactionSource, TransactionValidity};
+use transaction_payment::RuntimeDispatchInfo;
+
+use crate::rpc::methods::{ChainGetBlockHash, StateCall};
+use crate::std::{
+    AccountId, Api, ApiClientError, ApiResult, RpcClient, RuntimeMetadataPrefixed, RuntimeVersion,
+};
+use crate::{Balance, Hash, UncheckedExtrinsicV4};
+
+impl<P, Client> Api<P, Client>
+where
+    Client: RpcClient,
+{
+    /// Calls the runtime API function `method`, e.g. `Core_version`, with the SCALE encoded
+    /// `args` and decodes the result.
+    pub fn runtime_call<R: Decode>(
+        &self,
+        method: &str,
+        args: impl Encode,
+        at: Option<Hash>,
+    ) -> ApiResult<R> {
+        let data = Bytes(args.encode());
+        debug!("calling runtime api {} with {:?}", method, data);
+        let res = self.request::<StateCall>((method.to_string(), data, at))?;
+        R::decode(&mut res.0.as_slice()).map_err(|e| e.into())
+    }
+
+    /// Nonce of `account`, counted by the runtime.
+    pub fn runtime_account_nonce(&self, account: &AccountId, at: Option<Hash>) -> ApiResult<u32> {
+        self.runtime_call("AccountNonceApi_account_nonce", account, at)
+    }
+
+    /// Weight, class and fee of `xt`.
+    pub fn runtime_query_info<Call: Encode>(
+        &self,
+        xt: &UncheckedExtrinsicV4<Call>,
+        at: Option<Hash>,
+    ) -> ApiResult<RuntimeDispatchInfo<Balance>> {
+        let len = xt.encoded_size() as u32;
+        self.runtime_call("TransactionPaymentApi_query_info", (xt, len), at)
+    }
+
+    pub fn runtime_version(&self, at: Option<Hash>) -> ApiResult<RuntimeVersion> {
+        self.runtime_call("Core_version", (), at)
+    }
+
+    pub fn runtime_metadata(&self, at: Option<Hash>) -> ApiResult<RuntimeMetadataPrefixed> {
+        let metadata: Vec<u8> = self.runtime_call("Metadata_metadata", (), at)?;
+        RuntimeMetadataPrefixed::decode(&mut metadata.as_slice()).map_err(|e| e.into())
+    }
+
+    /// Validates `xt` like the transaction pool would, without submitting it. The validity is
+    /// checked on top of `at`, the best block by default.
+    pub fn validate_transaction<Call: Encode>(
+        &self,
+        source: TransactionSource,
+        xt: &UncheckedExtrinsicV4<Call>,
+        at: Option<Hash>,
+    ) -> ApiResult<TransactionValidity> {
+        let block_hash = match at {
+            Some(hash) => hash,
+            None => self
+                .request::<ChainGetBlockHash>((None,))?
+                .ok_or_else(|| ApiClientError::RpcClient("node has no best block".into()))?,
+        };
+        self.runtime_call(
+            "TaggedTransactionQueue_validate_transaction",
+            (source, xt, block_hash),
+            Some(block_hash),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::std::rpc::mock_node::{test_metadata, MockNode, RuntimeCall};
+    use crate::{GenericAddress, GenericExtra};
+    use sp_core::sr25519;
+    use sp_runtime::transaction_validity::{
+        InvalidTransaction, TransactionValidityError, ValidTransaction,
+    };
+    use sp_runtime::MultiSignature;
+    use support::weights::DispatchClass;
+
+    fn api(node: &MockNode) -> Api<sr25519::Pair, MockNode> {
+        Api::new(node.clone()).unwrap()
+    }
+
+    fn xt() -> UncheckedExtrinsicV4<[u8; 3]> {
+        UncheckedExtrinsicV4::new_signed(
+            [1, 1, 1],
+            GenericAddress::default(),
+            MultiSignature::default(),
+            GenericExtra::default(),
+        )
+    }
+
+    #[test]
+    fn runtime_calls_send_scale_encoded_args() {
+        let node = MockNode::new(test_metadata());
+        let api = api(&node);
+        node.set_runtime_call("AccountNonceApi_account_nonce", &5u32);
+
+        let alice = AccountId::from([1; 32]);
+        let at = Some(Hash::repeat_byte(3));
+        assert_eq!(api.runtime_account_nonce(&alice, at).unwrap(), 5);
+
+        assert_eq!(
+            node.runtime_calls()[0],
+            RuntimeCall {
+                method: "AccountNonceApi_account_nonce".to_string(),
+                args: vec![1; 32],
+                at,
+            }
+        );
+        assert!(matches!(
+            api.runtime_call::<u32>("Unknown_function", (), None),
+            Err(ApiClientError::Rpc { code: -32603, .. })
+        ));
+    }
+
+    #[test]
+    fn runtime_version_and_metadata_are_decoded() {
+        let node = MockNode::new(test_metadata());
+        let api = api(&node);
+        let version = RuntimeVersion {
+            spec_version: 9,
+            transaction_version: 2,
+            ..Default::default()
+        };
+        node.set_runtime_call("Core_version", &version);
+        node.set_runtime_call("Metadata_metadata", &test_metadata());
+
+        assert_eq!(api.runtime_version(None).unwrap(), version);
+        assert_eq!(
+            api.runtime_metadata(None).unwrap().encode(),
+            test_metadata()
+        );
+        let calls = node.runtime_calls();
+        assert_eq!(calls[0].args, Vec::<u8>::new());
+        assert_eq!(calls[1].method, "Metadata_metadata");
+    }
+
+    #[test]
+    fn query_info_sends_the_extrinsic_and_its_length() {
+        let node = MockNode::new(test_metadata());
+        let api = api(&node);
+        // weight 1000, `DispatchClass::Normal`, partial fee 42
+        let mut info = 1000u64.to_le_bytes().to_vec();
+        info.push(0);
+        info.extend_from_slice(&42u128.to_le_bytes());
+        node.set_opaque_runtime_call("TransactionPaymentApi_query_info", info);
+
+        let xt = xt();
+        let info = api.runtime_query_info(&xt, None).unwrap();
+        assert_eq!(info.weight, 1000);
+        assert_eq!(info.class, DispatchClass::Normal);
+        assert_eq!(info.partial_fee, 42);
+
+        let mut args = xt.encode();
+        args.extend_from_slice(&(xt.encoded_size() as u32).to_le_bytes());
+        assert_eq!(node.runtime_calls()[0].args, args);
+    }
+
+    #[test]
+    fn transactions_are_validated_on_top_of_the_best_block() {
+        let node = MockNode::new(test_metadata()).set_block_hash(Hash::repeat_byte(9));
+        let api = api(&node);
+        let valid = ValidTransaction {
+            priority: 1,
+            requires: vec![],
+            provides: vec![vec![1]],
+            longevity: 64,
+            propagate: true,
+        };
+        node.set_runtime_call::<TransactionValidity>(
+            "TaggedTransactionQueue_validate_transaction",
+            &Ok(valid.clone()),
+        );
+
+        let xt = xt();
+        assert_eq!(
+            api.validate_transaction(TransactionSource::External, &xt, None)
+                .unwrap(),
+            Ok(valid)
+        );
+
+        // `TransactionSource::External`, the extrinsic and the block hash
+        let call = &node.runtime_calls()[0];
+        let mut args = vec![2];
+        args.extend(xt.encode());
+        args.extend_from_slice(Hash::repeat_byte(9).as_bytes());
+        assert_eq!(call.args, args);
+        assert_eq!(call.at, Some(Hash::repeat_byte(9)));
+
+        // `Err(Invalid(Stale))`
+        node.set_opaque_runtime_call("TaggedTransactionQueue_validate_transaction", vec![1, 0, 3]);
+        assert_eq!(
+            api.validate_transaction(TransactionSource::External, &xt, None)
+                .unwrap(),
+            Err(TransactionValidityError::Invalid(InvalidTransaction::Stale))
+        );
+    }
+}
@@ -0,0 +1,153 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! Changes of storage entries over a range of blocks, with `state_queryStorage`.
+//!
+//! The node needs to keep the state of the queried blocks, i.e. an archive node is needed for
+//! blocks older than the pruning window.
+
+use codec::{Decode, Encode};
+use sp_core::storage::{StorageChangeSet, StorageKey};
+
+use crate::rpc::methods::{StateQueryStorage, StateQueryStorageAt};
+use crate::std::{Api, ApiClientError, ApiResult, RpcClient};
+use crate::Hash;
+
+/// A storage entry that changed in a block: the block hash, the key and the new value. The
+/// value is `None` if the entry was removed.
+pub type StorageChange<V> = (Hash, StorageKey, Option<V>);
+
+impl<P, Client> Api<P, Client>
+where
+    Client: RpcClient,
+{
+    /// All changes of `keys` in the blocks from `from` to `to`, the best block by default. The
+    /// first change set holds the values at `from`.
+    pub fn query_storage_changes(
+        &self,
+        keys: Vec<StorageKey>,
+        from: Hash,
+        to: Option<Hash>,
+    ) -> ApiResult<Vec<StorageChange<Vec<u8>>>> {
+        let change_sets = self.request::<StateQueryStorage>((keys, from, to))?;
+        Ok(flatten(change_sets))
+    }
+
+    /// Values of `keys` at block `at`, the best block by default.
+    pub fn query_storage_changes_at(
+        &self,
+        keys: Vec<StorageKey>,
+        at: Option<Hash>,
+    ) -> ApiResult<Vec<StorageChange<Vec<u8>>>> {
+        let change_sets = self.request::<StateQueryStorageAt>((keys, at))?;
+        Ok(flatten(change_sets))
+    }
+
+    /// Like `query_storage_changes`, but for the storage value `storage_key_name` of the module
+    /// `storage_prefix`, with the values decoded.
+    pub fn query_storage_value_changes<V: Decode>(
+        &self,
+        storage_prefix: &'static str,
+        storage_key_name: &'static str,
+        from: Hash,
+        to: Option<Hash>,
+    ) -> ApiResult<Vec<(Hash, Option<V>)>> {
+        let key = self
+            .metadata
+            .storage_value_key(storage_prefix, storage_key_name)?;
+        let changes = decode(self.query_storage_changes(vec![key], from, to)?)?;
+        Ok(changes
+            .into_iter()
+            .map(|(block, _, value)| (block, value))
+            .collect())
+    }
+
+    /// Like `query_storage_changes`, but for the entries `map_keys` of the storage map
+    /// `storage_key_name`, with the values decoded.
+    pub fn query_storage_map_changes<K: Encode, V: Decode + Clone>(
+        &self,
+        storage_prefix: &'static str,
+        storage_key_name: &'static str,
+        map_keys: Vec<K>,
+        from: Hash,
+        to: Option<Hash>,
+    ) -> ApiResult<Vec<StorageChange<V>>> {
+        let keys = map_keys
+            .into_iter()
+            .map(|map_key| {
+                self.metadata
+                    .storage_map_key::<K, V>(storage_prefix, storage_key_name, map_key)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        decode(self.query_storage_changes(keys, from, to)?)
+    }
+}
+
+fn flatten(change_sets: Vec<StorageChangeSet<Hash>>) -> Vec<StorageChange<Vec<u8>>> {
+    change_sets
+        .into_iter()
+        .flat_map(|set| {
+            let block = set.block;
+            set.changes
+                .into_iter()
+                .map(move |(key, data)| (block, key, data.map(|d| d.0)))
+        })
+        .collect()
+}
+
+fn decode<V: Decode>(changes: Vec<StorageChange<Vec<u8>>>) -> ApiResult<Vec<StorageChange<V>>> {
+    changes
+        .into_iter()
+        .map(|(block, key, value)| {
+            let value = value
+                .map(|v| V::decode(&mut v.as_slice()))
+                .transpose()
+                .map_err(ApiClientError::from)?;
+            Ok((block, key, value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sp_core::storage::StorageData;
+
+    #[test]
+    fn change_sets_are_flattened_and_decoded() {
+        let key = StorageKey(vec![1]);
+        let change_sets = vec![
+            StorageChangeSet {
+                block: Hash::repeat_byte(1),
+                changes: vec![(key.clone(), Some(StorageData(10u128.encode())))],
+            },
+            StorageChangeSet {
+                block: Hash::repeat_byte(2),
+                changes: vec![(key.clone(), None)],
+            },
+        ];
+
+        let changes: Vec<StorageChange<u128>> = decode(flatten(change_sets)).unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                (Hash::repeat_byte(1), key.clone(), Some(10)),
+                (Hash::repeat_byte(2), key, None),
+            ]
+        );
+    }
+}
@@ -0,0 +1,352 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! Iteration over all entries of a storage map, one page of keys at a time.
+//!
+//! ```no_run
+//! use sp_core::sr25519;
+//! use substrate_api_client::rpc::WsRpcClient;
+//! use substrate_api_client::{AccountId, AccountInfo, Api};
+//!
+//! let api = Api::<sr25519::Pair, _>::new(WsRpcClient::new("ws://127.0.0.1:9944")).unwrap();
+//! let accounts = api
+//!     .iter_storage_map::<AccountId, AccountInfo>("System", "Account", None)
+//!     .unwrap()
+//!     .set_prefetch(true);
+//! for account in accounts {
+//!     let (account_id, info) = account.unwrap();
+//!     println!("{}: {:?}", account_id, info.data);
+//! }
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::marker::PhantomData;
+
+use codec::{Decode, Encode};
+use sp_core::storage::StorageKey;
+
+use crate::rpc::methods::StateGetKeysPaged;
+use crate::std::node_metadata::MetadataError;
+use crate::std::{Api, ApiClientError, ApiResult, RpcClient};
+use crate::Hash;
+
+const DEFAULT_PAGE_SIZE: u32 = 100;
+
+type DecodeKeyFn<K> = Box<dyn Fn(&StorageKey) -> Result<K, MetadataError>>;
+
+/// Iterator over the `(key, value)` pairs of a storage map, created by
+/// `Api::iter_storage_map` or `Api::iter_storage_double_map`.
+///
+/// Keys are fetched with `state_getKeysPaged`. Without prefetch, every value is fetched on its
+/// own when the iterator reaches it. With prefetch, the values of a whole page are fetched with
+/// one `state_queryStorageAt` request. Entries removed while iterating are skipped. Unless a block
+/// is given, pages may be read from different blocks.
+pub struct StorageMapIter<'a, P, Client: RpcClient, K, V> {
+    api: &'a Api<P, Client>,
+    prefix: StorageKey,
+    decode_key: DecodeKeyFn<K>,
+    at: Option<Hash>,
+    page_size: u32,
+    prefetch: bool,
+    keys: VecDeque<StorageKey>,
+    values: VecDeque<Option<Vec<u8>>>,
+    start_key: Option<StorageKey>,
+    last_page: bool,
+    _marker: PhantomData<V>,
+}
+
+impl<'a, P, Client: RpcClient, K, V: Decode> StorageMapIter<'a, P, Client, K, V> {
+    fn new(
+        api: &'a Api<P, Client>,
+        prefix: StorageKey,
+        decode_key: DecodeKeyFn<K>,
+        at: Option<Hash>,
+    ) -> Self {
+        Self {
+            api,
+            prefix,
+            decode_key,
+            at,
+            page_size: DEFAULT_PAGE_SIZE,
+            prefetch: false,
+            keys: VecDeque::new(),
+            values: VecDeque::new(),
+            start_key: None,
+            last_page: false,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Number of keys fetched per request. The node allows at most 1000.
+    pub fn set_page_size(mut self, page_size: u32) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    /// Fetches the values of a page together, instead of one by one.
+    pub fn set_prefetch(mut self, prefetch: bool) -> Self {
+        self.prefetch = prefetch;
+        self
+    }
+
+    fn fetch_page(&mut self) -> ApiResult<()> {
+        let keys = self.api.request::<StateGetKeysPaged>((
+            self.prefix.clone(),
+            self.page_size,
+            self.start_key.clone(),
+            self.at,
+        ))?;
+        self.last_page = (keys.len() as u32) < self.page_size;
+        self.start_key = keys.last().cloned();
+
+        if self.prefetch && !keys.is_empty() {
+            let mut values: HashMap<StorageKey, Option<Vec<u8>>> = self
+                .api
+                .query_storage_changes_at(keys.clone(), self.at)?
+                .into_iter()
+                .map(|(_, key, value)| (key, value))
+                .collect();
+            self.values = keys
+                .iter()
+                .map(|key| values.remove(key).flatten())
+                .collect();
+        }
+        self.keys = keys.into();
+        Ok(())
+    }
+
+    fn next_entry(&mut self) -> ApiResult<Option<(StorageKey, Option<Vec<u8>>)>> {
+        if self.keys.is_empty() {
+            if self.last_page {
+                return Ok(None);
+            }
+            self.fetch_page()?;
+        }
+        let key = match self.keys.pop_front() {
+            Some(key) => key,
+            None => return Ok(None),
+        };
+        let value = if self.prefetch {
+            self.values.pop_front().flatten()
+        } else {
+            self.api
+                .get_opaque_storage_by_key_hash(key.clone(), self.at)?
+        };
+        Ok(Some((key, value)))
+    }
+}
+
+impl<'a, P, Client: RpcClient, K, V: Decode> Iterator for StorageMapIter<'a, P, Client, K, V> {
+    type Item = ApiResult<(K, V)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (key, value) = match self.next_entry() {
+                Ok(Some((key, Some(value)))) => (key, value),
+                // removed since its key was listed
+                Ok(Some((_, None))) => continue,
+                Ok(None) => return None,
+                Err(e) => {
+                    // do not retry the failed page forever
+                    self.keys.clear();
+                    self.last_page = true;
+                    return Some(Err(e));
+                }
+            };
+            let entry = (self.decode_key)(&key)
+                .map_err(ApiClientError::from)
+                .and_then(|k| Ok((k, V::decode(&mut value.as_slice())?)));
+            return Some(entry);
+        }
+    }
+}
+
+impl<P, Client> Api<P, Client>
+where
+    Client: RpcClient,
+{
+    /// Iterates over all entries of the storage map `storage_key_name` of the module
+    /// `storage_prefix` at block `at`. The map has to use a hasher that keeps the key, like
+    /// `Blake2_128Concat`, `Twox64Concat` or `Identity`.
+    pub fn iter_storage_map<K, V>(
+        &self,
+        storage_prefix: &'static str,
+        storage_key_name: &'static str,
+        at: Option<Hash>,
+    ) -> ApiResult<StorageMapIter<'_, P, Client, K, V>>
+    where
+        K: Encode + Decode + 'static,
+        V: Decode + Clone + 'static,
+    {
+        let storage = self
+            .metadata
+            .module(storage_prefix)?
+            .storage(storage_key_name)?;
+        let map = storage.get_map::<K, V>()?;
+        let decode_key = Box::new(move |key: &StorageKey| map.decode_key(key));
+        Ok(StorageMapIter::new(
+            self,
+            storage.get_map_prefix()?,
+            decode_key,
+            at,
+        ))
+    }
+
+    /// Like `iter_storage_map`, but for a double map. Both keys are returned.
+    pub fn iter_storage_double_map<K, Q, V>(
+        &self,
+        storage_prefix: &'static str,
+        storage_key_name: &'static str,
+        at: Option<Hash>,
+    ) -> ApiResult<StorageMapIter<'_, P, Client, (K, Q), V>>
+    where
+        K: Encode + Decode + 'static,
+        Q: Encode + Decode + 'static,
+        V: Decode + Clone + 'static,
+    {
+        let storage = self
+            .metadata
+            .module(storage_prefix)?
+            .storage(storage_key_name)?;
+        let map = storage.get_double_map::<K, Q, V>()?;
+        let decode_key = Box::new(move |key: &StorageKey| map.decode_key(key));
+        Ok(StorageMapIter::new(
+            self,
+            storage.get_map_prefix()?,
+            decode_key,
+            at,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::std::rpc::mock_node::test_metadata;
+    use crate::std::rpc::MockNode;
+    use crate::std::{AccountId, Value};
+    use crate::AccountInfo;
+    use sp_core::sr25519;
+
+    fn api(node: &MockNode) -> Api<sr25519::Pair, MockNode> {
+        Api::new(node.clone()).unwrap()
+    }
+
+    fn insert_accounts(node: &MockNode, count: u8) -> Vec<(AccountId, AccountInfo)> {
+        let api = api(node);
+        (0..count)
+            .map(|i| {
+                let account = AccountId::from([i; 32]);
+                let info = AccountInfo {
+                    nonce: i.into(),
+                    ..Default::default()
+                };
+                let key = api
+                    .metadata
+                    .storage_map_key::<AccountId, AccountInfo>("System", "Account", account.clone())
+                    .unwrap();
+                node.insert_storage(key, &info);
+                (account, info)
+            })
+            .collect()
+    }
+
+    fn sorted<K: Ord, V>(mut entries: Vec<(K, V)>) -> Vec<(K, V)> {
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries
+    }
+
+    #[test]
+    fn map_entries_are_fetched_page_by_page() {
+        let node = MockNode::new(test_metadata());
+        let accounts = insert_accounts(&node, 5);
+        let api = api(&node);
+
+        // `take` ends the test if a page is fetched again
+        let entries = api
+            .iter_storage_map::<AccountId, AccountInfo>("System", "Account", None)
+            .unwrap()
+            .set_page_size(2)
+            .take(10)
+            .collect::<ApiResult<Vec<_>>>()
+            .unwrap();
+        assert_eq!(sorted(entries), accounts);
+    }
+
+    #[test]
+    fn prefetched_values_come_with_their_page() {
+        let node = MockNode::new(test_metadata());
+        let accounts = insert_accounts(&node, 3);
+        let api = api(&node);
+        // values are only served by `state_queryStorageAt` from now on
+        node.set_response("state_getStorage", Value::Null);
+
+        let fetched_one_by_one = api
+            .iter_storage_map::<AccountId, AccountInfo>("System", "Account", None)
+            .unwrap()
+            .count();
+        assert_eq!(fetched_one_by_one, 0);
+
+        let entries = api
+            .iter_storage_map::<AccountId, AccountInfo>("System", "Account", None)
+            .unwrap()
+            .set_page_size(2)
+            .set_prefetch(true)
+            .take(10)
+            .collect::<ApiResult<Vec<_>>>()
+            .unwrap();
+        assert_eq!(sorted(entries), accounts);
+    }
+
+    #[test]
+    fn double_map_entries_come_with_both_keys() {
+        let node = MockNode::new(test_metadata());
+        // entries of other maps are not listed
+        insert_accounts(&node, 1);
+        let api = api(&node);
+        let alice = AccountId::from([1; 32]);
+        let bob = AccountId::from([2; 32]);
+        for (asset, account, balance) in
+            vec![(7u32, &alice, 100u128), (8, &alice, 200), (8, &bob, 300)]
+        {
+            let key = api
+                .metadata
+                .storage_double_map_key::<u32, AccountId, u128>(
+                    "Assets",
+                    "Account",
+                    asset,
+                    account.clone(),
+                )
+                .unwrap();
+            node.insert_storage(key, &balance);
+        }
+
+        let entries = api
+            .iter_storage_double_map::<u32, AccountId, u128>("Assets", "Account", None)
+            .unwrap()
+            .collect::<ApiResult<Vec<_>>>()
+            .unwrap();
+        assert_eq!(
+            sorted(entries),
+            vec![
+                ((7, alice.clone()), 100),
+                ((8, alice), 200),
+                ((8, bob), 300)
+            ]
+        );
+    }
+}